@@ -0,0 +1,62 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use realms::noisemap::{FalloffParameters, NoiseMap, NoiseParameters};
+
+const SEED: u64 = 42;
+const SIZES: [usize; 3] = [256, 512, 1024];
+const OCTAVES: [usize; 3] = [1, 4, 8];
+
+fn parameters(octaves: usize) -> NoiseParameters {
+    NoiseParameters {
+        frequency: 4.0,
+        octaves,
+        persistence: 0.4,
+        lacunarity: 3.5,
+        rotate_octaves: false,
+        preserve_aspect: true,
+        antialias: false,
+        wrap_x: false,
+        decorrelate_octaves: false,
+        domain_offset: (0.0, 0.0),
+    }
+}
+
+fn noise_map(c: &mut Criterion) {
+    for &octaves in &OCTAVES {
+        let parameters = parameters(octaves);
+        let mut group = c.benchmark_group(format!("new/{} octaves", octaves));
+        group.sample_size(10);
+
+        for &size in &SIZES {
+            group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+                b.iter(|| NoiseMap::<f64>::new(SEED, None, size, size, black_box(&parameters)))
+            });
+        }
+        group.finish();
+    }
+}
+
+fn noise_map_with_falloff(c: &mut Criterion) {
+    let parameters = parameters(8);
+    let falloff = FalloffParameters::default();
+    let mut group = c.benchmark_group("new_with_falloff/8 octaves");
+    group.sample_size(10);
+
+    for &size in &SIZES {
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+            b.iter(|| {
+                NoiseMap::<f64>::new_with_falloff(
+                    SEED,
+                    size,
+                    size,
+                    black_box(&parameters),
+                    &falloff,
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, noise_map, noise_map_with_falloff);
+criterion_main!(benches);
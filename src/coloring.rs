@@ -0,0 +1,833 @@
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+#[cfg(feature = "serde")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::noisemap::Float;
+use crate::util::{inverse_lerp, lerp, linear_to_srgb, srgb_to_linear};
+use crate::world::World;
+
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+pub const OBLIQUE_RELIEF: f64 = 0.25;
+pub const HILLSHADE_RELIEF: f64 = 0.05;
+pub const DEFAULT_SUN_AZIMUTH: f64 = 315.0;
+pub const DEFAULT_SUN_ALTITUDE: f64 = 45.0;
+pub const HYPSOMETRIC_TINTS: [Color; 6] = [
+    Color::rgb(112, 153, 89),
+    Color::rgb(164, 186, 115),
+    Color::rgb(222, 214, 163),
+    Color::rgb(191, 153, 105),
+    Color::rgb(153, 115, 87),
+    Color::rgb(250, 250, 250),
+];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b, a: 255 }
+    }
+
+    pub fn from_hex(hex: &str) -> Result<Self, HexColorError> {
+        let channels = Self::parse_hex(hex, 3)?;
+        Ok(Self::rgb(channels[0], channels[1], channels[2]))
+    }
+
+    pub fn from_hex_rgba(hex: &str) -> Result<Self, HexColorError> {
+        let channels = Self::parse_hex(hex, 4)?;
+        Ok(Self {
+            r: channels[0],
+            g: channels[1],
+            b: channels[2],
+            a: channels[3],
+        })
+    }
+
+    fn parse_hex(hex: &str, channels: usize) -> Result<Vec<u8>, HexColorError> {
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+
+        if let Some(c) = digits.chars().find(|c| !c.is_ascii_hexdigit()) {
+            return Err(HexColorError::InvalidCharacter(c));
+        }
+        if digits.len() != channels * 2 {
+            return Err(HexColorError::InvalidLength(digits.len()));
+        }
+
+        Ok((0..channels)
+            .map(|i| u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16).unwrap())
+            .collect())
+    }
+
+    pub fn over(&self, background: &Color) -> Self {
+        let alpha = self.a as f64 / 255.0;
+        let background_alpha = background.a as f64 / 255.0 * (1.0 - alpha);
+        let out_alpha = alpha + background_alpha;
+
+        if out_alpha == 0.0 {
+            return Self {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 0,
+            };
+        }
+
+        let blend = |front: u8, back: u8| {
+            ((front as f64 * alpha + back as f64 * background_alpha) / out_alpha).round() as u8
+        };
+
+        Self {
+            r: blend(self.r, background.r),
+            g: blend(self.g, background.g),
+            b: blend(self.b, background.b),
+            a: (out_alpha * 255.0).round() as u8,
+        }
+    }
+
+    pub fn to_hsv(&self) -> (f64, f64, f64) {
+        let (r, g, b) = (
+            self.r as f64 / 255.0,
+            self.g as f64 / 255.0,
+            self.b as f64 / 255.0,
+        );
+        let max = r.max(g).max(b);
+        let delta = max - r.min(g).min(b);
+
+        let hue = if delta == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / delta).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / delta + 2.0)
+        } else {
+            60.0 * ((r - g) / delta + 4.0)
+        };
+        let saturation = if max == 0.0 { 0.0 } else { delta / max };
+
+        (hue, saturation, max)
+    }
+
+    pub fn from_hsv(hue: f64, saturation: f64, value: f64) -> Self {
+        let [r, g, b] = Self::hsv_channels(hue, saturation, value);
+        Self::rgb(
+            (r * 255.0).round() as u8,
+            (g * 255.0).round() as u8,
+            (b * 255.0).round() as u8,
+        )
+    }
+
+    fn hsv_channels(hue: f64, saturation: f64, value: f64) -> [f64; 3] {
+        let chroma = value * saturation;
+        let sector = hue.rem_euclid(360.0) / 60.0;
+        let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+        let m = value - chroma;
+
+        let (r, g, b) = match sector as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        [r + m, g + m, b + m]
+    }
+
+    pub fn to_hex(&self) -> String {
+        if self.a == 255 {
+            format!("#{:02X}{:02X}{:02X}", self.r, self.g, self.b)
+        } else {
+            format!("#{:02X}{:02X}{:02X}{:02X}", self.r, self.g, self.b, self.a)
+        }
+    }
+
+    pub fn shade(self, factor: f64) -> Self {
+        Self {
+            r: (self.r as f64 * factor) as u8,
+            g: (self.g as f64 * factor) as u8,
+            b: (self.b as f64 * factor) as u8,
+            a: self.a,
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<u8> {
+        vec![self.r, self.g, self.b, self.a]
+    }
+}
+
+impl From<Color> for Vec<u8> {
+    fn from(color: Color) -> Self {
+        color.into_vec()
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum HexColorError {
+    InvalidLength(usize),
+    InvalidCharacter(char),
+}
+
+impl fmt::Display for HexColorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HexColorError::InvalidLength(length) => {
+                write!(f, "expected 6 or 8 hex digits, found {}", length)
+            }
+            HexColorError::InvalidCharacter(c) => write!(f, "invalid hex digit '{}'", c),
+        }
+    }
+}
+
+impl Error for HexColorError {}
+
+// 8 digits carry an alpha channel, anything else is read as #RRGGBB
+impl FromStr for Color {
+    type Err = HexColorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix('#').unwrap_or(s).len() {
+            8 => Color::from_hex_rgba(s),
+            _ => Color::from_hex(s),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        hex.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Colors {
+    pub sea_low: Color,
+    pub sea_high: Color,
+    pub land_low: Color,
+    pub land_high: Color,
+    pub seabed: Color,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Gradient {
+    stops: Vec<Color>,
+}
+
+impl Gradient {
+    pub fn new(stops: Vec<Color>) -> Self {
+        assert!(!stops.is_empty(), "a gradient needs at least one color");
+        Self { stops }
+    }
+
+    // comma separated stops such as "#233F54,#219C35,#F0F0F0"
+    pub fn from_hex_stops(stops: &str) -> Result<Self, HexColorError> {
+        let stops = stops
+            .split(',')
+            .map(|stop| stop.trim().parse())
+            .collect::<Result<_, _>>()?;
+        Ok(Self::new(stops))
+    }
+
+    pub fn from_image<P: AsRef<Path>>(path: P) -> image::ImageResult<Self> {
+        let image = image::open(path)?.to_rgba();
+        let stops = (0..image.width())
+            .map(|x| {
+                let [r, g, b, a] = image.get_pixel(x, 0).0;
+                Color { r, g, b, a }
+            })
+            .collect();
+
+        Ok(Self::new(stops))
+    }
+
+    pub fn sample(&self, value: f64, threshold: f64, space: ColorSpace) -> Color {
+        let position = value.clamp(0.0, 1.0) * (self.stops.len() - 1) as f64;
+        let index = (position as usize).min(self.stops.len() - 1);
+        let next = (index + 1).min(self.stops.len() - 1);
+
+        interpolate_colors(
+            &self.stops[index],
+            &self.stops[next],
+            position - index as f64,
+            threshold,
+            space,
+        )
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ViewMode {
+    Terrain,
+    Atlas,
+    Grayscale,
+    Oblique,
+    Gradient,
+    Relief,
+}
+
+impl ViewMode {
+    const ALL: [ViewMode; 6] = [
+        ViewMode::Terrain,
+        ViewMode::Atlas,
+        ViewMode::Grayscale,
+        ViewMode::Oblique,
+        ViewMode::Gradient,
+        ViewMode::Relief,
+    ];
+
+    pub fn index(self) -> usize {
+        Self::ALL.iter().position(|&mode| mode == self).unwrap()
+    }
+
+    pub fn next(self) -> Self {
+        Self::ALL[(self.index() + 1) % Self::ALL.len()]
+    }
+
+    pub fn previous(self) -> Self {
+        Self::ALL[(self.index() + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ColorSpace {
+    Rgb,
+    LinearRgb,
+    Hsv,
+}
+
+impl ColorSpace {
+    const ALL: [ColorSpace; 3] = [ColorSpace::Rgb, ColorSpace::LinearRgb, ColorSpace::Hsv];
+
+    pub fn index(self) -> usize {
+        Self::ALL.iter().position(|&space| space == self).unwrap()
+    }
+
+    pub fn next(self) -> Self {
+        Self::ALL[(self.index() + 1) % Self::ALL.len()]
+    }
+
+    pub fn previous(self) -> Self {
+        Self::ALL[(self.index() + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+}
+
+// Everything that decides the color of a pixel, so that the viewer and headless rendering
+// produce the same buffer. Coordinates are in buffer pixels, `supersample` per cell.
+pub struct Renderer<'a, T: Float> {
+    pub world: &'a World<T>,
+    pub colors: &'a Colors,
+    pub mode: ViewMode,
+    pub gradient: Option<&'a Gradient>,
+    pub hypsometric_tints: &'a Gradient,
+    pub color_space: ColorSpace,
+    pub water_depth_scale: f64,
+    pub vertical_scale: f64,
+    pub dither: bool,
+    pub supersample: usize,
+    pub ambient_occlusion: &'a [f64],
+    pub occlusion_strength: f64,
+    pub hillshade: &'a [f64],
+}
+
+impl<'a, T: Float> Renderer<'a, T> {
+    // returns the RGBA buffer with its width and height
+    pub fn render(&self) -> (Vec<u8>, usize, usize) {
+        if self.mode == ViewMode::Oblique {
+            return self.oblique_buffer();
+        }
+
+        let width = self.world.parameters.width * self.supersample;
+        let height = self.world.parameters.height * self.supersample;
+
+        if self.mode == ViewMode::Grayscale && self.supersample == 1 && !self.dither {
+            return (self.world.elevation.to_rgba_bytes(), width, height);
+        }
+
+        let buffer = (0..width * height)
+            .flat_map(|i| self.pixel_color(i % width, i / width).into_vec())
+            .collect();
+        (buffer, width, height)
+    }
+
+    fn oblique_buffer(&self) -> (Vec<u8>, usize, usize) {
+        let width = self.world.parameters.width;
+        let height = self.world.parameters.height;
+        let sea_level = self.world.sea_level;
+        let relief = height as f64 * OBLIQUE_RELIEF * self.vertical_scale.max(0.0);
+
+        let buffer_height = height + relief.ceil() as usize;
+        let mut buffer = vec![0; width * buffer_height * 4];
+
+        for x in 0..width {
+            let mut horizon = buffer_height;
+
+            for y in (0..height).rev() {
+                let value = self.world.elevation.get(x, y);
+                let lift = self.world.elevation.normalize(value.max(sea_level)) * relief;
+                let top = (y as f64 + relief - lift).max(0.0) as usize;
+
+                if top < horizon {
+                    let color = self.elevation_color(value, 0.0).into_vec();
+                    for row in top..horizon {
+                        let i = (row * width + x) * 4;
+                        buffer[i..i + 4].copy_from_slice(&color);
+                    }
+                    horizon = top;
+                }
+            }
+        }
+
+        (buffer, width, buffer_height)
+    }
+
+    pub fn pixel_color(&self, x: usize, y: usize) -> Color {
+        let value = self.pixel_elevation(x, y);
+
+        match self.mode {
+            ViewMode::Terrain | ViewMode::Oblique => self
+                .elevation_color(value, self.dither_threshold(x, y))
+                .shade(self.ambient_occlusion_factor(x, y)),
+            ViewMode::Atlas => self.atlas_color(x, y, value),
+            ViewMode::Gradient => match self.gradient {
+                Some(gradient) => gradient.sample(
+                    self.world.elevation.normalize(value),
+                    self.dither_threshold(x, y),
+                    self.color_space,
+                ),
+                None => self.elevation_color(value, self.dither_threshold(x, y)),
+            },
+            ViewMode::Relief => self.relief_color(x, y, value),
+            ViewMode::Grayscale => {
+                let gray = (self.world.elevation.normalize(value) * 255.0
+                    + self.dither_threshold(x, y)) as u8;
+                Color::rgb(gray, gray, gray)
+            }
+        }
+    }
+
+    pub fn pixel_elevation(&self, x: usize, y: usize) -> f64 {
+        if self.supersample == 1 {
+            self.world.elevation.get(x, y)
+        } else {
+            self.world.elevation.sample(
+                (x as f64 + 0.5) / self.supersample as f64 - 0.5,
+                (y as f64 + 0.5) / self.supersample as f64 - 0.5,
+            )
+        }
+    }
+
+    pub fn elevation_color(&self, value: f64, threshold: f64) -> Color {
+        let color = terrain_color(self.world, self.colors, value, threshold, self.color_space);
+        let elevation = &self.world.elevation;
+        let sea_level = self.world.sea_level;
+
+        if value < sea_level && self.water_depth_scale > 0.0 {
+            let height = inverse_lerp(elevation.min, sea_level.min(elevation.max), value);
+            let opacity = ((1.0 - height) * self.water_depth_scale).min(1.0);
+            Color {
+                a: (opacity * 255.0) as u8,
+                ..color
+            }
+            .over(&self.colors.seabed)
+        } else {
+            color
+        }
+    }
+
+    fn ambient_occlusion_factor(&self, x: usize, y: usize) -> f64 {
+        if self.ambient_occlusion.is_empty() {
+            return 1.0;
+        }
+
+        let occlusion = self.ambient_occlusion[self.cell_index(x, y)];
+        lerp(1.0, occlusion, self.occlusion_strength.min(1.0))
+    }
+
+    fn relief_color(&self, x: usize, y: usize, value: f64) -> Color {
+        let threshold = self.dither_threshold(x, y);
+        let sea_level = self.world.sea_level;
+
+        if value < sea_level {
+            return self.elevation_color(value, threshold);
+        }
+
+        self.hypsometric_tints
+            .sample(
+                inverse_lerp(
+                    sea_level.max(self.world.elevation.min),
+                    self.world.elevation.max,
+                    value,
+                ),
+                threshold,
+                self.color_space,
+            )
+            .shade(self.hillshade[self.cell_index(x, y)])
+    }
+
+    fn atlas_color(&self, x: usize, y: usize, value: f64) -> Color {
+        let sea_level = self.world.sea_level;
+
+        if value < sea_level {
+            return self.colors.sea_high;
+        }
+
+        let width = self.world.parameters.width * self.supersample;
+        let height = self.world.parameters.height * self.supersample;
+        let neighbors = [
+            (x.wrapping_sub(1), y),
+            (x + 1, y),
+            (x, y.wrapping_sub(1)),
+            (x, y + 1),
+        ];
+        let on_coast = neighbors
+            .iter()
+            .any(|&(x, y)| x < width && y < height && self.pixel_elevation(x, y) < sea_level);
+
+        if on_coast {
+            Color::rgb(0, 0, 0)
+        } else {
+            self.colors.land_low
+        }
+    }
+
+    fn dither_threshold(&self, x: usize, y: usize) -> f64 {
+        if self.dither {
+            (BAYER_4X4[y % 4][x % 4] as f64 + 0.5) / 16.0
+        } else {
+            0.0
+        }
+    }
+
+    fn cell_index(&self, x: usize, y: usize) -> usize {
+        (y / self.supersample) * self.world.parameters.width + x / self.supersample
+    }
+}
+
+pub fn hillshade<T: Float>(
+    world: &World<T>,
+    azimuth: f64,
+    altitude: f64,
+    vertical_scale: f64,
+) -> Vec<f64> {
+    (0..world.parameters.height)
+        .flat_map(|y| hillshade_row(world, y, azimuth, altitude, vertical_scale))
+        .collect()
+}
+
+pub fn hillshade_row<T: Float>(
+    world: &World<T>,
+    y: usize,
+    azimuth: f64,
+    altitude: f64,
+    vertical_scale: f64,
+) -> Vec<f64> {
+    hillshade_span(
+        world,
+        y,
+        0,
+        world.parameters.width,
+        azimuth,
+        altitude,
+        vertical_scale,
+    )
+}
+
+pub fn hillshade_span<T: Float>(
+    world: &World<T>,
+    y: usize,
+    x0: usize,
+    x1: usize,
+    azimuth: f64,
+    altitude: f64,
+    vertical_scale: f64,
+) -> Vec<f64> {
+    world.elevation.hillshade_span(
+        y,
+        x0,
+        x1,
+        azimuth.to_radians(),
+        altitude.to_radians(),
+        vertical_scale * HILLSHADE_RELIEF * world.parameters.width as f64,
+    )
+}
+
+// Renders like the viewer does with its default display settings, without a window.
+pub fn color_buffer<T: Float>(world: &World<T>, colors: &Colors, mode: ViewMode) -> Vec<u8> {
+    let hypsometric_tints = Gradient::new(HYPSOMETRIC_TINTS.to_vec());
+    let hillshade = if mode == ViewMode::Relief {
+        hillshade(world, DEFAULT_SUN_AZIMUTH, DEFAULT_SUN_ALTITUDE, 1.0)
+    } else {
+        vec![]
+    };
+
+    let renderer = Renderer {
+        world,
+        colors,
+        mode,
+        gradient: None,
+        hypsometric_tints: &hypsometric_tints,
+        color_space: ColorSpace::Rgb,
+        water_depth_scale: 0.0,
+        vertical_scale: 1.0,
+        dither: false,
+        supersample: 1,
+        ambient_occlusion: &[],
+        occlusion_strength: 0.0,
+        hillshade: &hillshade,
+    };
+    renderer.render().0
+}
+
+// sea and land colors without water depth, as used by vector exports
+pub fn terrain_color<T: Float>(
+    world: &World<T>,
+    colors: &Colors,
+    value: f64,
+    threshold: f64,
+    space: ColorSpace,
+) -> Color {
+    let elevation = &world.elevation;
+    let sea_level = world.sea_level;
+
+    if value < sea_level {
+        interpolate_colors(
+            &colors.sea_low,
+            &colors.sea_high,
+            inverse_lerp(elevation.min, sea_level.min(elevation.max), value),
+            threshold,
+            space,
+        )
+    } else {
+        interpolate_colors(
+            &colors.land_low,
+            &colors.land_high,
+            inverse_lerp(sea_level.max(elevation.min), elevation.max, value),
+            threshold,
+            space,
+        )
+    }
+}
+
+pub fn interpolate_colors(
+    a: &Color,
+    b: &Color,
+    value: f64,
+    threshold: f64,
+    space: ColorSpace,
+) -> Color {
+    match space {
+        ColorSpace::Rgb | ColorSpace::LinearRgb => {
+            let linear = space == ColorSpace::LinearRgb;
+            Color {
+                r: interpolate_u8(a.r, b.r, value, threshold, linear),
+                g: interpolate_u8(a.g, b.g, value, threshold, linear),
+                b: interpolate_u8(a.b, b.b, value, threshold, linear),
+                a: interpolate_u8(a.a, b.a, value, threshold, false),
+            }
+        }
+        ColorSpace::Hsv => {
+            let (hue_a, saturation_a, value_a) = a.to_hsv();
+            let (hue_b, saturation_b, value_b) = b.to_hsv();
+
+            let mut hue_delta = hue_b - hue_a;
+            if hue_delta > 180.0 {
+                hue_delta -= 360.0;
+            } else if hue_delta < -180.0 {
+                hue_delta += 360.0;
+            }
+
+            let [red, green, blue] = Color::hsv_channels(
+                hue_a + hue_delta * value,
+                lerp(saturation_a, saturation_b, value),
+                lerp(value_a, value_b, value),
+            );
+            Color {
+                r: quantize(red * 255.0, threshold),
+                g: quantize(green * 255.0, threshold),
+                b: quantize(blue * 255.0, threshold),
+                a: interpolate_u8(a.a, b.a, value, threshold, false),
+            }
+        }
+    }
+}
+
+fn interpolate_u8(a: u8, b: u8, value: f64, threshold: f64, linear: bool) -> u8 {
+    if linear {
+        let mixed = lerp(
+            srgb_to_linear(a as f64 / 255.0),
+            srgb_to_linear(b as f64 / 255.0),
+            value,
+        );
+        quantize(linear_to_srgb(mixed) * 255.0, threshold)
+    } else {
+        quantize(lerp(a as f64, b as f64, value), threshold)
+    }
+}
+
+fn quantize(value: f64, threshold: f64) -> u8 {
+    (value + threshold) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::WorldParameters;
+
+    fn colors() -> Colors {
+        Colors {
+            sea_low: Color::rgb(20, 40, 80),
+            sea_high: Color::rgb(50, 100, 150),
+            land_low: Color::rgb(40, 150, 50),
+            land_high: Color::rgb(240, 240, 240),
+            seabed: Color::rgb(60, 50, 40),
+        }
+    }
+
+    // FNV-1a, so that buffers compare by a value that doesn't depend on the standard library
+    fn hash(buffer: &[u8]) -> u64 {
+        buffer.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+
+    // in a buffer of the ramp, which is 8 cells wide
+    fn pixel(buffer: &[u8], x: usize, y: usize) -> Color {
+        let i = (y * 8 + x) * 4;
+        Color {
+            r: buffer[i],
+            g: buffer[i + 1],
+            b: buffer[i + 2],
+            a: buffer[i + 3],
+        }
+    }
+
+    // elevation rising from the top left corner to the bottom right one, independent of the noise
+    fn ramp() -> World {
+        let mut world = World::new(1, WorldParameters::world_map(4));
+        let last = world.elevation.map.len() - 1;
+        for (i, value) in world.elevation.map.iter_mut().enumerate() {
+            *value = i as f64 / last as f64 * 2.0 - 1.0;
+        }
+        world.elevation.update_bounds();
+        world.sea_level = 0.0;
+        world
+    }
+
+    #[test]
+    fn hex_colors_parse_by_length() {
+        assert_eq!("#239C35".parse(), Ok(Color::rgb(35, 156, 53)));
+        assert_eq!(
+            "239c3580".parse(),
+            Ok(Color {
+                r: 35,
+                g: 156,
+                b: 53,
+                a: 128
+            })
+        );
+        assert_eq!(
+            "#+39C35".parse::<Color>(),
+            Err(HexColorError::InvalidCharacter('+'))
+        );
+        assert_eq!(
+            "#239C3".parse::<Color>(),
+            Err(HexColorError::InvalidLength(5))
+        );
+        assert_eq!(
+            Gradient::from_hex_stops("#000000, #FFFFFF"),
+            Ok(Gradient::new(vec![
+                Color::rgb(0, 0, 0),
+                Color::rgb(255, 255, 255)
+            ]))
+        );
+    }
+
+    #[test]
+    fn hsv_round_trips() {
+        for &color in &[
+            Color::rgb(0, 0, 0),
+            Color::rgb(255, 255, 255),
+            Color::rgb(128, 128, 128),
+            Color::rgb(255, 0, 0),
+            Color::rgb(33, 156, 53),
+            Color::rgb(51, 98, 153),
+            Color::rgb(250, 10, 200),
+        ] {
+            let (hue, saturation, value) = color.to_hsv();
+            assert_eq!(Color::from_hsv(hue, saturation, value), color);
+        }
+
+        assert_eq!(Color::from_hsv(120.0, 1.0, 1.0), Color::rgb(0, 255, 0));
+        assert_eq!(Color::from_hsv(-120.0, 1.0, 1.0), Color::rgb(0, 0, 255));
+    }
+
+    #[test]
+    fn fixed_seed_renders_the_same_buffer() {
+        let parameters = WorldParameters::world_map(32);
+        let render = |seed| {
+            color_buffer(
+                &World::<f64>::new(seed, parameters),
+                &colors(),
+                ViewMode::Terrain,
+            )
+        };
+
+        let buffer = render(42);
+        assert_eq!(buffer.len(), 64 * 32 * 4);
+        assert_eq!(hash(&buffer), hash(&render(42)));
+        assert_ne!(hash(&buffer), hash(&render(43)));
+    }
+
+    #[test]
+    fn ramp_renders_the_expected_pixels() {
+        let world = ramp();
+        let colors = colors();
+        let render = |mode| color_buffer(&world, &colors, mode);
+
+        // the ramp runs from the lowest sea at (0, 0) through the coast between rows 1 and 2 to
+        // the highest land at (7, 3)
+        let terrain = render(ViewMode::Terrain);
+        assert_eq!(pixel(&terrain, 0, 0), colors.sea_low);
+        assert_eq!(pixel(&terrain, 7, 1), Color::rgb(49, 98, 147));
+        assert_eq!(pixel(&terrain, 0, 2), Color::rgb(46, 152, 56));
+        assert_eq!(pixel(&terrain, 7, 3), colors.land_high);
+
+        let grayscale = render(ViewMode::Grayscale);
+        assert_eq!(pixel(&grayscale, 0, 0), Color::rgb(0, 0, 0));
+        assert_eq!(pixel(&grayscale, 7, 1), Color::rgb(123, 123, 123));
+        assert_eq!(pixel(&grayscale, 0, 2), Color::rgb(131, 131, 131));
+        assert_eq!(pixel(&grayscale, 7, 3), Color::rgb(255, 255, 255));
+
+        // flat sea, the coastline drawn along the first land row, flat land behind it
+        let atlas = render(ViewMode::Atlas);
+        assert_eq!(pixel(&atlas, 0, 0), colors.sea_high);
+        assert_eq!(pixel(&atlas, 7, 1), colors.sea_high);
+        assert_eq!(pixel(&atlas, 3, 2), Color::rgb(0, 0, 0));
+        assert_eq!(pixel(&atlas, 3, 3), colors.land_low);
+
+        // only land is shaded
+        let relief = render(ViewMode::Relief);
+        assert_eq!(relief[..8 * 2 * 4], terrain[..8 * 2 * 4]);
+        assert_eq!(pixel(&relief, 0, 2), Color::rgb(88, 116, 68));
+        assert_eq!(pixel(&relief, 7, 3), Color::rgb(183, 183, 183));
+    }
+}
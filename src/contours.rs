@@ -0,0 +1,128 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::noisemap::{Float, NoiseMap};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContourBand {
+    pub level: f64,
+    pub loops: Vec<Vec<(f64, f64)>>,
+}
+
+// A crossing is identified by the grid edge it lies on: (x, y, horizontal), where a horizontal
+// edge joins (x, y) to (x + 1, y) and a vertical one joins (x, y) to (x, y + 1).
+type Edge = (usize, usize, bool);
+
+pub fn contour_bands<T: Float>(map: &NoiseMap<T>, interval: f64) -> Vec<ContourBand> {
+    assert!(interval > 0.0, "contour interval must be positive");
+
+    let mut bands = vec![];
+    let mut level = (map.min / interval).ceil() * interval;
+    while level <= map.max {
+        bands.push(ContourBand {
+            level,
+            loops: contour_loops(map, level),
+        });
+        level += interval;
+    }
+    bands
+}
+
+pub fn contour_loops<T: Float>(map: &NoiseMap<T>, level: f64) -> Vec<Vec<(f64, f64)>> {
+    // The map is padded with a border below every level so that all contours close.
+    let width = map.width() + 2;
+    let height = map.height() + 2;
+    let border = map.min - 1.0;
+    let value = |x: usize, y: usize| {
+        if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+            border
+        } else {
+            map.get(x - 1, y - 1)
+        }
+    };
+
+    let mut links: HashMap<Edge, Vec<Edge>> = HashMap::new();
+    let mut link = |a: Edge, b: Edge| {
+        links.entry(a).or_default().push(b);
+        links.entry(b).or_default().push(a);
+    };
+
+    for y in 0..height - 1 {
+        for x in 0..width - 1 {
+            let top = (x, y, true);
+            let bottom = (x, y + 1, true);
+            let left = (x, y, false);
+            let right = (x + 1, y, false);
+
+            let case = (value(x, y) >= level) as u8 * 8
+                + (value(x + 1, y) >= level) as u8 * 4
+                + (value(x + 1, y + 1) >= level) as u8 * 2
+                + (value(x, y + 1) >= level) as u8;
+
+            match case {
+                1 | 14 => link(left, bottom),
+                2 | 13 => link(bottom, right),
+                3 | 12 => link(left, right),
+                4 | 11 => link(top, right),
+                6 | 9 => link(top, bottom),
+                7 | 8 => link(left, top),
+                5 => {
+                    link(left, top);
+                    link(bottom, right);
+                }
+                10 => {
+                    link(top, right);
+                    link(left, bottom);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let position = |(x, y, horizontal): Edge| {
+        let (x1, y1) = if horizontal { (x + 1, y) } else { (x, y + 1) };
+        let (a, b) = (value(x, y), value(x1, y1));
+        let t = ((level - a) / (b - a)).clamp(0.0, 1.0);
+        (
+            x as f64 + (x1 as f64 - x as f64) * t - 1.0,
+            y as f64 + (y1 as f64 - y as f64) * t - 1.0,
+        )
+    };
+
+    let mut visited = HashSet::new();
+    let mut loops = vec![];
+    let mut starts: Vec<Edge> = links.keys().copied().collect();
+    starts.sort_unstable();
+
+    for start in starts {
+        if !visited.insert(start) {
+            continue;
+        }
+
+        let mut path = vec![position(start)];
+        let mut current = start;
+        while let Some(&next) = links[&current].iter().find(|edge| !visited.contains(*edge)) {
+            visited.insert(next);
+            path.push(position(next));
+            current = next;
+        }
+        loops.push(path);
+    }
+
+    loops
+}
+
+pub fn svg_path(loops: &[Vec<(f64, f64)>]) -> String {
+    loops
+        .iter()
+        .filter(|points| points.len() > 2)
+        .map(|points| {
+            let mut d = format!("M{:.2},{:.2}", points[0].0 + 0.5, points[0].1 + 0.5);
+            for &(x, y) in &points[1..] {
+                d.push_str(&format!("L{:.2},{:.2}", x + 0.5, y + 0.5));
+            }
+            d.push('Z');
+            d
+        })
+        .collect::<Vec<_>>()
+        .join("")
+}
@@ -0,0 +1,124 @@
+use crate::noisemap::{Float, NoiseMap};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ErosionParameters {
+    pub inertia: f64,
+    pub capacity: f64,
+    pub min_capacity: f64,
+    pub deposition: f64,
+    pub erosion: f64,
+    pub evaporation: f64,
+    pub gravity: f64,
+    pub lifetime: usize,
+}
+
+impl Default for ErosionParameters {
+    fn default() -> Self {
+        Self {
+            inertia: 0.05,
+            capacity: 4.0,
+            min_capacity: 0.01,
+            deposition: 0.3,
+            erosion: 0.3,
+            evaporation: 0.01,
+            gravity: 4.0,
+            lifetime: 30,
+        }
+    }
+}
+
+pub fn erode_droplet<T: Float>(
+    map: &mut NoiseMap<T>,
+    start: (f64, f64),
+    parameters: &ErosionParameters,
+) -> (usize, usize, usize, usize) {
+    let (mut x, mut y) = start;
+    let (mut direction_x, mut direction_y) = (0.0, 0.0);
+    let mut speed = 1.0;
+    let mut water = 1.0;
+    let mut sediment = 0.0;
+
+    let mut bounds = (x as usize, y as usize, x as usize + 2, y as usize + 2);
+
+    for _ in 0..parameters.lifetime {
+        let (height, gradient_x, gradient_y) = height_and_gradient(map, x, y);
+
+        direction_x = direction_x * parameters.inertia - gradient_x * (1.0 - parameters.inertia);
+        direction_y = direction_y * parameters.inertia - gradient_y * (1.0 - parameters.inertia);
+        let length = (direction_x * direction_x + direction_y * direction_y).sqrt();
+        if length == 0.0 {
+            break;
+        }
+        direction_x /= length;
+        direction_y /= length;
+
+        let (old_x, old_y) = (x, y);
+        x += direction_x;
+        y += direction_y;
+
+        if x < 0.0 || y < 0.0 || x >= (map.width() - 1) as f64 || y >= (map.height() - 1) as f64 {
+            break;
+        }
+
+        bounds = (
+            bounds.0.min(x as usize),
+            bounds.1.min(y as usize),
+            bounds.2.max(x as usize + 2),
+            bounds.3.max(y as usize + 2),
+        );
+
+        let delta = map.sample(x, y) - height;
+        let capacity = f64::max(
+            -delta * speed * water * parameters.capacity,
+            parameters.min_capacity,
+        );
+
+        if sediment > capacity || delta > 0.0 {
+            let deposit = if delta > 0.0 {
+                delta.min(sediment)
+            } else {
+                (sediment - capacity) * parameters.deposition
+            };
+            sediment -= deposit;
+            distribute(map, old_x, old_y, deposit);
+        } else {
+            let eroded = f64::min((capacity - sediment) * parameters.erosion, -delta);
+            sediment += eroded;
+            distribute(map, old_x, old_y, -eroded);
+        }
+
+        speed = (speed * speed - delta * parameters.gravity).max(0.0).sqrt();
+        water *= 1.0 - parameters.evaporation;
+    }
+
+    bounds
+}
+
+fn height_and_gradient<T: Float>(map: &NoiseMap<T>, x: f64, y: f64) -> (f64, f64, f64) {
+    let (x0, y0) = (x as usize, y as usize);
+    let (u, v) = (x - x0 as f64, y - y0 as f64);
+
+    let top_left = map.get(x0, y0);
+    let top_right = map.get(x0 + 1, y0);
+    let bottom_left = map.get(x0, y0 + 1);
+    let bottom_right = map.get(x0 + 1, y0 + 1);
+
+    let gradient_x = (top_right - top_left) * (1.0 - v) + (bottom_right - bottom_left) * v;
+    let gradient_y = (bottom_left - top_left) * (1.0 - u) + (bottom_right - top_right) * u;
+
+    (map.sample(x, y), gradient_x, gradient_y)
+}
+
+fn distribute<T: Float>(map: &mut NoiseMap<T>, x: f64, y: f64, amount: f64) {
+    let (x0, y0) = (x as usize, y as usize);
+    let (u, v) = (x - x0 as f64, y - y0 as f64);
+
+    for &(cell_x, cell_y, weight) in &[
+        (x0, y0, (1.0 - u) * (1.0 - v)),
+        (x0 + 1, y0, u * (1.0 - v)),
+        (x0, y0 + 1, (1.0 - u) * v),
+        (x0 + 1, y0 + 1, u * v),
+    ] {
+        map.set(cell_x, cell_y, map.get(cell_x, cell_y) + amount * weight);
+    }
+}
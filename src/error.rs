@@ -0,0 +1,58 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use ggez::GameError;
+
+#[derive(Debug)]
+pub enum RealmsError {
+    Context(GameError),
+    Font(GameError),
+    InvalidParameters(String),
+    Io(io::Error),
+    Image(image::ImageError),
+    Png(png::EncodingError),
+}
+
+impl fmt::Display for RealmsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RealmsError::Context(e) => write!(f, "could not create ggez context: {}", e),
+            RealmsError::Font(e) => write!(f, "could not load font: {}", e),
+            RealmsError::InvalidParameters(reason) => write!(f, "invalid parameters: {}", reason),
+            RealmsError::Io(e) => write!(f, "{}", e),
+            RealmsError::Image(e) => write!(f, "{}", e),
+            RealmsError::Png(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for RealmsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RealmsError::Context(e) | RealmsError::Font(e) => Some(e),
+            RealmsError::InvalidParameters(_) => None,
+            RealmsError::Io(e) => Some(e),
+            RealmsError::Image(e) => Some(e),
+            RealmsError::Png(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for RealmsError {
+    fn from(e: io::Error) -> Self {
+        RealmsError::Io(e)
+    }
+}
+
+impl From<image::ImageError> for RealmsError {
+    fn from(e: image::ImageError) -> Self {
+        RealmsError::Image(e)
+    }
+}
+
+impl From<png::EncodingError> for RealmsError {
+    fn from(e: png::EncodingError) -> Self {
+        RealmsError::Png(e)
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::str::FromStr;
+
+use realms::noisemap::{FalloffParameters, NoiseParameters, PolarParameters};
+use realms::world::WorldParameters;
+
+const HEADER: &str = "# realms favorites v1";
+const HELP: &str = "# one favorite per line as key=value fields; falloff is a,b,multiplier,edges \
+                    with the edges that fall off out of tblr, polar is band,transition,multiplier, \
+                    either is - when disabled, and a sea_level ending in % is a percentile";
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Favorite {
+    pub seed: u64,
+    pub parameters: WorldParameters,
+}
+
+impl Favorite {
+    // one line of the favorites file
+    pub fn to_line(self) -> String {
+        let parameters = &self.parameters;
+        let noise = &parameters.elevation_parameters;
+        let falloff = match &parameters.falloff {
+            Some(falloff) => {
+                let edges: String = [
+                    (falloff.top, 't'),
+                    (falloff.bottom, 'b'),
+                    (falloff.left, 'l'),
+                    (falloff.right, 'r'),
+                ]
+                .iter()
+                .filter(|&&(enabled, _)| enabled)
+                .map(|&(_, letter)| letter)
+                .collect();
+                format!(
+                    "{},{},{},{}",
+                    falloff.a, falloff.b, falloff.multiplier, edges
+                )
+            }
+            None => "-".to_string(),
+        };
+        let polar = match &parameters.polar {
+            Some(polar) => format!("{},{},{}", polar.band, polar.transition, polar.multiplier),
+            None => "-".to_string(),
+        };
+
+        format!(
+            "seed={} width={} height={} sea_level={} units_per_cell={} frequency={} octaves={} \
+             persistence={} lacunarity={} rotate_octaves={} preserve_aspect={} antialias={} \
+             wrap_x={} decorrelate_octaves={} domain_offset={},{} falloff={} polar={}",
+            self.seed,
+            parameters.width,
+            parameters.height,
+            parameters.sea_level,
+            parameters.units_per_cell,
+            noise.frequency,
+            noise.octaves,
+            noise.persistence,
+            noise.lacunarity,
+            noise.rotate_octaves,
+            noise.preserve_aspect,
+            noise.antialias,
+            noise.wrap_x,
+            noise.decorrelate_octaves,
+            noise.domain_offset.0,
+            noise.domain_offset.1,
+            falloff,
+            polar,
+        )
+    }
+
+    pub fn from_line(line: &str) -> io::Result<Self> {
+        let fields = line
+            .split_whitespace()
+            .map(|field| {
+                field
+                    .split_once('=')
+                    .ok_or_else(|| invalid_data(format!("invalid favorite field: {}", field)))
+            })
+            .collect::<io::Result<HashMap<_, _>>>()?;
+        let field = |key: &str| {
+            fields
+                .get(key)
+                .copied()
+                .ok_or_else(|| invalid_data(format!("missing {} in favorite: {}", key, line)))
+        };
+
+        let domain_offset = match field("domain_offset")?.split(',').collect::<Vec<_>>()[..] {
+            [x, y] => (parse(x)?, parse(y)?),
+            _ => return Err(invalid_data(format!("invalid favorite field: {}", line))),
+        };
+        let falloff = match field("falloff")? {
+            "-" => None,
+            falloff => match falloff.split(',').collect::<Vec<_>>()[..] {
+                [a, b, multiplier, edges] => Some(FalloffParameters {
+                    a: parse(a)?,
+                    b: parse(b)?,
+                    multiplier: parse(multiplier)?,
+                    top: edges.contains('t'),
+                    bottom: edges.contains('b'),
+                    left: edges.contains('l'),
+                    right: edges.contains('r'),
+                }),
+                _ => return Err(invalid_data(format!("invalid falloff: {}", falloff))),
+            },
+        };
+        let polar = match field("polar")? {
+            "-" => None,
+            polar => match polar.split(',').collect::<Vec<_>>()[..] {
+                [band, transition, multiplier] => Some(PolarParameters {
+                    band: parse(band)?,
+                    transition: parse(transition)?,
+                    multiplier: parse(multiplier)?,
+                }),
+                _ => return Err(invalid_data(format!("invalid polar: {}", polar))),
+            },
+        };
+
+        Ok(Self {
+            seed: parse(field("seed")?)?,
+            parameters: WorldParameters {
+                width: parse(field("width")?)?,
+                height: parse(field("height")?)?,
+                elevation_parameters: NoiseParameters {
+                    frequency: parse(field("frequency")?)?,
+                    octaves: parse(field("octaves")?)?,
+                    persistence: parse(field("persistence")?)?,
+                    lacunarity: parse(field("lacunarity")?)?,
+                    rotate_octaves: parse(field("rotate_octaves")?)?,
+                    preserve_aspect: parse(field("preserve_aspect")?)?,
+                    antialias: parse(field("antialias")?)?,
+                    wrap_x: parse(field("wrap_x")?)?,
+                    decorrelate_octaves: parse(field("decorrelate_octaves")?)?,
+                    domain_offset,
+                },
+                falloff,
+                polar,
+                sea_level: parse(field("sea_level")?)?,
+                units_per_cell: parse(field("units_per_cell")?)?,
+            },
+        })
+    }
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Vec<Favorite>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+
+    let mut lines = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty());
+    match lines.next() {
+        Some(HEADER) | None => {}
+        Some(header) => {
+            return Err(invalid_data(format!(
+                "unsupported favorites format: {}",
+                header
+            )))
+        }
+    }
+
+    lines
+        .filter(|line| !line.starts_with('#'))
+        .map(Favorite::from_line)
+        .collect()
+}
+
+pub fn save<P: AsRef<Path>>(path: P, favorites: &[Favorite]) -> io::Result<()> {
+    let mut contents = format!("{}\n{}\n", HEADER, HELP);
+    for favorite in favorites {
+        contents.push_str(&favorite.to_line());
+        contents.push('\n');
+    }
+    fs::write(path, contents)
+}
+
+fn parse<T: FromStr>(field: &str) -> io::Result<T> {
+    field
+        .parse()
+        .map_err(|_| invalid_data(format!("invalid favorite field: {}", field)))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use realms::world::SeaLevelMode;
+
+    fn favorites() -> Vec<Favorite> {
+        let mut island = WorldParameters::world_map(32);
+        island.polar = None;
+        island.falloff = Some(FalloffParameters {
+            top: false,
+            ..FalloffParameters::default()
+        });
+        island.sea_level = SeaLevelMode::Absolute(-0.25);
+        island.elevation_parameters.domain_offset = (1.5, -3.0);
+
+        vec![
+            Favorite {
+                seed: 7,
+                parameters: WorldParameters::world_map(32),
+            },
+            Favorite {
+                seed: u64::MAX,
+                parameters: island,
+            },
+        ]
+    }
+
+    #[test]
+    fn lines_parse_back() {
+        for favorite in favorites() {
+            assert_eq!(Favorite::from_line(&favorite.to_line()).unwrap(), favorite);
+        }
+    }
+
+    #[test]
+    fn saved_favorites_load_back() {
+        let path = std::env::temp_dir().join("realms-favorites-round-trip.txt");
+        save(&path, &favorites()).unwrap();
+        let loaded = load(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.unwrap(), favorites());
+    }
+
+    #[test]
+    fn files_without_the_version_header_are_rejected() {
+        let path = std::env::temp_dir().join("realms-favorites-no-header.txt");
+        fs::write(&path, format!("{}\n", favorites()[0].to_line())).unwrap();
+        let loaded = load(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+}
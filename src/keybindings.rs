@@ -0,0 +1,381 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+
+use ggez::event::KeyCode;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Action {
+    Regenerate,
+    FloodPreview,
+    ToggleErosion,
+    ErosionStep,
+    Variant,
+    Wavelength,
+    CopyParameters,
+    Randomize,
+    Sweep,
+    ExportBiomes,
+    ExportAsc,
+    ExportJson,
+    ExportSvg,
+    ExportResampled,
+    ExportLegend,
+    ToggleLabels,
+    SaveFavorite,
+    PreviousFavorite,
+    NextFavorite,
+    Screenshot,
+    RerollOctave,
+    RerollDetail,
+    JumpHighest,
+    JumpLowest,
+    FitToWindow,
+    PreviousGroup,
+    NextGroup,
+    Group1,
+    Group2,
+    Group3,
+    Group4,
+    PreviousRow,
+    NextRow,
+    ResetRow,
+    Increase,
+    Decrease,
+    Press,
+    NextTool,
+    SunSweep,
+    Help,
+}
+
+impl Action {
+    pub const ALL: [Action; 40] = [
+        Action::Regenerate,
+        Action::FloodPreview,
+        Action::ToggleErosion,
+        Action::ErosionStep,
+        Action::Variant,
+        Action::Wavelength,
+        Action::CopyParameters,
+        Action::Randomize,
+        Action::Sweep,
+        Action::ExportBiomes,
+        Action::ExportAsc,
+        Action::ExportJson,
+        Action::ExportSvg,
+        Action::ExportResampled,
+        Action::ExportLegend,
+        Action::ToggleLabels,
+        Action::SaveFavorite,
+        Action::PreviousFavorite,
+        Action::NextFavorite,
+        Action::Screenshot,
+        Action::RerollOctave,
+        Action::RerollDetail,
+        Action::JumpHighest,
+        Action::JumpLowest,
+        Action::FitToWindow,
+        Action::PreviousGroup,
+        Action::NextGroup,
+        Action::Group1,
+        Action::Group2,
+        Action::Group3,
+        Action::Group4,
+        Action::PreviousRow,
+        Action::NextRow,
+        Action::ResetRow,
+        Action::Increase,
+        Action::Decrease,
+        Action::Press,
+        Action::NextTool,
+        Action::SunSweep,
+        Action::Help,
+    ];
+
+    pub fn repeats(self) -> bool {
+        matches!(
+            self,
+            Action::ErosionStep
+                | Action::PreviousFavorite
+                | Action::NextFavorite
+                | Action::JumpHighest
+                | Action::JumpLowest
+                | Action::FitToWindow
+                | Action::PreviousGroup
+                | Action::NextGroup
+                | Action::Group1
+                | Action::Group2
+                | Action::Group3
+                | Action::Group4
+                | Action::PreviousRow
+                | Action::NextRow
+                | Action::ResetRow
+                | Action::Increase
+                | Action::Decrease
+                | Action::Press
+        )
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Regenerate => "generate a new world",
+            Action::FloodPreview => "hold to preview flooding, scroll to adjust",
+            Action::ToggleErosion => "toggle erosion stepping",
+            Action::ErosionStep => "erode one droplet while stepping",
+            Action::Variant => "generate a close variant",
+            Action::Wavelength => "show the dominant wavelength",
+            Action::CopyParameters => "copy parameters to the clipboard",
+            Action::Randomize => "randomize parameters",
+            Action::Sweep => "export a sweep of the selected row",
+            Action::ExportBiomes => "export the biome map",
+            Action::ExportAsc => "export an ASCII elevation grid",
+            Action::ExportJson => "export metadata as JSON",
+            Action::ExportSvg => "export a vector map",
+            Action::ExportResampled => "export the map at four times its size",
+            Action::ExportLegend => "export the color legend",
+            Action::ToggleLabels => "toggle labels",
+            Action::SaveFavorite => "save the seed as a favorite",
+            Action::PreviousFavorite => "previous favorite",
+            Action::NextFavorite => "next favorite",
+            Action::Screenshot => "save a screenshot",
+            Action::RerollOctave => "reroll the selected octave",
+            Action::RerollDetail => "reroll the selected octave and all finer ones",
+            Action::JumpHighest => "jump to the highest point",
+            Action::JumpLowest => "jump to the lowest point",
+            Action::FitToWindow => "fit the map to the window",
+            Action::PreviousGroup => "previous page",
+            Action::NextGroup => "next page",
+            Action::Group1 => "page 1",
+            Action::Group2 => "page 2",
+            Action::Group3 => "page 3",
+            Action::Group4 => "page 4",
+            Action::PreviousRow => "previous row",
+            Action::NextRow => "next row",
+            Action::ResetRow => "reset the row to its default",
+            Action::Increase => "increase the row value",
+            Action::Decrease => "decrease the row value",
+            Action::Press => "toggle the row",
+            Action::NextTool => "switch the mouse tool",
+            Action::SunSweep => "toggle the moving sun",
+            Action::Help => "toggle this help",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|action| format!("{:?}", action) == name)
+    }
+}
+
+const KEYS: [KeyCode; 68] = [
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::Up,
+    KeyCode::Down,
+    KeyCode::Left,
+    KeyCode::Right,
+    KeyCode::Home,
+    KeyCode::End,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::Insert,
+    KeyCode::Delete,
+    KeyCode::Back,
+    KeyCode::Return,
+    KeyCode::Space,
+    KeyCode::Tab,
+    KeyCode::Comma,
+    KeyCode::Period,
+    KeyCode::Slash,
+    KeyCode::Minus,
+    KeyCode::Equals,
+    KeyCode::Escape,
+];
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    KEYS.iter()
+        .copied()
+        .find(|key| format!("{:?}", key) == name)
+}
+
+// A key bound to several actions tries them in declaration order, until one consumes the key.
+pub struct KeyBindings {
+    bindings: Vec<(Action, KeyCode)>,
+}
+
+impl KeyBindings {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut key_bindings = Self::default();
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(key_bindings),
+            Err(e) => return Err(e),
+        };
+
+        for line in contents.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid =
+                || io::Error::new(ErrorKind::InvalidData, format!("invalid binding: {}", line));
+            let mut parts = line.splitn(2, '=');
+            let action = parts
+                .next()
+                .and_then(|name| Action::parse(name.trim()))
+                .ok_or_else(invalid)?;
+            let keys = parts
+                .next()
+                .ok_or_else(invalid)?
+                .split(',')
+                .map(|name| parse_key(name.trim()).ok_or_else(invalid))
+                .collect::<io::Result<Vec<_>>>()?;
+
+            key_bindings.bind(action, &keys);
+        }
+
+        Ok(key_bindings)
+    }
+
+    pub fn bind(&mut self, action: Action, keys: &[KeyCode]) {
+        self.bindings.retain(|&(bound, _)| bound != action);
+        self.bindings.extend(keys.iter().map(|&key| (action, key)));
+    }
+
+    pub fn actions(&self, key: KeyCode) -> Vec<Action> {
+        let mut actions: Vec<Action> = self
+            .bindings
+            .iter()
+            .filter(|&&(_, bound)| bound == key)
+            .map(|&(action, _)| action)
+            .collect();
+        actions.sort_by_key(|&action| action as usize);
+        actions
+    }
+
+    pub fn is_bound(&self, key: KeyCode, action: Action) -> bool {
+        self.bindings.contains(&(action, key))
+    }
+
+    pub fn keys(&self, action: Action) -> Vec<KeyCode> {
+        self.bindings
+            .iter()
+            .filter(|&&(bound, _)| bound == action)
+            .map(|&(_, key)| key)
+            .collect()
+    }
+
+    pub fn help(&self) -> String {
+        Action::ALL
+            .iter()
+            .filter_map(|&action| {
+                let keys = self.keys(action);
+                if keys.is_empty() {
+                    return None;
+                }
+
+                let keys: Vec<String> = keys.iter().map(|key| format!("{:?}", key)).collect();
+                Some(format!("{:>16}  {}", keys.join(", "), action.description()))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            bindings: vec![
+                (Action::Regenerate, KeyCode::Return),
+                (Action::FloodPreview, KeyCode::F),
+                (Action::ToggleErosion, KeyCode::E),
+                (Action::ErosionStep, KeyCode::Space),
+                (Action::Variant, KeyCode::V),
+                (Action::Wavelength, KeyCode::M),
+                (Action::CopyParameters, KeyCode::C),
+                (Action::Randomize, KeyCode::X),
+                (Action::Sweep, KeyCode::S),
+                (Action::ExportBiomes, KeyCode::B),
+                (Action::ExportAsc, KeyCode::G),
+                (Action::ExportJson, KeyCode::J),
+                (Action::ExportSvg, KeyCode::K),
+                (Action::ExportResampled, KeyCode::I),
+                (Action::ExportLegend, KeyCode::O),
+                (Action::ToggleLabels, KeyCode::N),
+                (Action::SaveFavorite, KeyCode::Y),
+                (Action::PreviousFavorite, KeyCode::Comma),
+                (Action::NextFavorite, KeyCode::Period),
+                (Action::Screenshot, KeyCode::P),
+                (Action::RerollOctave, KeyCode::R),
+                (Action::RerollDetail, KeyCode::D),
+                (Action::JumpHighest, KeyCode::H),
+                (Action::JumpLowest, KeyCode::L),
+                (Action::FitToWindow, KeyCode::Home),
+                (Action::PreviousGroup, KeyCode::PageUp),
+                (Action::NextGroup, KeyCode::PageDown),
+                (Action::Group1, KeyCode::Key1),
+                (Action::Group2, KeyCode::Key2),
+                (Action::Group3, KeyCode::Key3),
+                (Action::Group4, KeyCode::Key4),
+                (Action::PreviousRow, KeyCode::Up),
+                (Action::NextRow, KeyCode::Down),
+                (Action::ResetRow, KeyCode::Back),
+                (Action::Increase, KeyCode::Right),
+                (Action::Decrease, KeyCode::Left),
+                (Action::Press, KeyCode::Space),
+                (Action::NextTool, KeyCode::T),
+                (Action::SunSweep, KeyCode::U),
+                (Action::Help, KeyCode::F1),
+                (Action::Help, KeyCode::Slash),
+            ],
+        }
+    }
+}
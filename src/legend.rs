@@ -0,0 +1,59 @@
+// 3x5 bitmap glyphs, one row per byte with the low three bits used, for labelling exported images.
+const GLYPH_WIDTH: usize = 3;
+const GLYPH_HEIGHT: usize = 5;
+const GLYPHS: [(char, [u8; GLYPH_HEIGHT]); 12] = [
+    ('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+    ('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+    ('2', [0b111, 0b001, 0b111, 0b100, 0b111]),
+    ('3', [0b111, 0b001, 0b111, 0b001, 0b111]),
+    ('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+    ('5', [0b111, 0b100, 0b111, 0b001, 0b111]),
+    ('6', [0b111, 0b100, 0b111, 0b101, 0b111]),
+    ('7', [0b111, 0b001, 0b010, 0b010, 0b010]),
+    ('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+    ('9', [0b111, 0b101, 0b111, 0b001, 0b111]),
+    ('-', [0b000, 0b000, 0b111, 0b000, 0b000]),
+    ('.', [0b000, 0b000, 0b000, 0b000, 0b010]),
+];
+
+pub fn text_height(scale: usize) -> usize {
+    GLYPH_HEIGHT * scale
+}
+
+pub fn draw_text(
+    buffer: &mut [u8],
+    width: usize,
+    (x, y): (usize, usize),
+    text: &str,
+    scale: usize,
+    color: [u8; 4],
+) {
+    let height = buffer.len() / 4 / width;
+
+    for (i, character) in text.chars().enumerate() {
+        let rows = match GLYPHS.iter().find(|&&(glyph, _)| glyph == character) {
+            Some((_, rows)) => rows,
+            None => continue,
+        };
+        let left = x + i * (GLYPH_WIDTH + 1) * scale;
+
+        for (row, bits) in rows.iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - column)) == 0 {
+                    continue;
+                }
+
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let px = left + column * scale + dx;
+                        let py = y + row * scale + dy;
+                        if px < width && py < height {
+                            let index = (py * width + px) * 4;
+                            buffer[index..index + 4].copy_from_slice(&color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
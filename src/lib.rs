@@ -0,0 +1,11 @@
+#![warn(clippy::all)]
+
+pub mod coloring;
+pub mod contours;
+pub mod erosion;
+pub mod error;
+pub mod names;
+pub mod noisemap;
+pub mod pathfinding;
+pub mod util;
+pub mod world;
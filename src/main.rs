@@ -1,23 +1,29 @@
 #![warn(clippy::all)]
 
 use ggez::conf::WindowSetup;
-use ggez::{conf::WindowMode, event, graphics::Font, ContextBuilder, GameResult};
+use ggez::{
+    conf::WindowMode,
+    event,
+    graphics::{self, Font},
+    Context, ContextBuilder,
+};
 use rand::{thread_rng, RngCore};
 
-use noisemap::{FalloffParameters, NoiseParameters};
+use realms::coloring::{Color, Colors, Gradient};
+use realms::error::RealmsError;
+use realms::noisemap::{FalloffParameters, NoiseParameters};
+use realms::world::{SeaLevelMode, World, WorldParameters};
 
-use crate::{
-    viewer::{Color, Colors, WorldViewer},
-    world::{World, WorldParameters},
-};
+use crate::viewer::WorldViewer;
 
-mod noisemap;
-mod util;
-#[macro_use]
+mod favorites;
+mod keybindings;
+mod legend;
 mod viewer;
-mod world;
 
-fn main() -> GameResult {
+const WORLD_MAP_HEIGHT: usize = 250;
+
+fn main() -> Result<(), RealmsError> {
     let (mut ctx, mut event_loop) = ContextBuilder::new("Realms", "KBanana")
         .window_mode(
             WindowMode::default()
@@ -26,31 +32,60 @@ fn main() -> GameResult {
         )
         .window_setup(WindowSetup::default().title("Realms"))
         .build()
-        .expect("could not create ggez context!");
+        .map_err(RealmsError::Context)?;
 
-    let world = World::new(
-        thread_rng().next_u64(),
+    // realms [gradient.png | #RRGGBB,#RRGGBB,...] [--font font.ttf] [--world-map]
+    let mut args = std::env::args().skip(1);
+    let mut gradient_arg = None;
+    let mut font_path = None;
+    let mut world_map = false;
+    while let Some(arg) = args.next() {
+        if arg == "--font" {
+            font_path = args.next();
+        } else if arg == "--world-map" {
+            world_map = true;
+        } else {
+            gradient_arg = Some(arg);
+        }
+    }
+
+    let parameters = if world_map {
+        WorldParameters::world_map(WORLD_MAP_HEIGHT)
+    } else {
         WorldParameters {
             width: 500,
             height: 500,
-            sea_level: 0.0,
+            sea_level: SeaLevelMode::Absolute(0.0),
+            units_per_cell: 1.0,
             elevation_parameters: NoiseParameters {
-                scale: 0.25,
+                frequency: 4.0,
                 octaves: 8,
                 persistence: 0.4,
                 lacunarity: 3.5,
+                rotate_octaves: false,
+                preserve_aspect: true,
+                antialias: false,
+                wrap_x: false,
+                decorrelate_octaves: false,
+                domain_offset: (0.0, 0.0),
             },
             falloff: Some(FalloffParameters {
                 a: 2.0,
                 b: 6.0,
                 multiplier: 0.7,
+                top: true,
+                bottom: true,
+                left: true,
+                right: true,
             }),
-        },
-    );
+            polar: None,
+        }
+    };
+    let world = World::try_new(thread_rng().next_u64(), parameters)?;
 
     println!("World generated");
 
-    let font = Font::new_glyph_font_bytes(&mut ctx, include_bytes!("Px437_IBM_VGA_9x16.ttf"))?;
+    let font = load_font(&mut ctx, font_path.as_deref());
 
     let mut viewer = WorldViewer::new(
         world,
@@ -59,9 +94,24 @@ fn main() -> GameResult {
             sea_high: Color::rgb(51, 98, 153),
             land_low: Color::rgb(33, 156, 53),
             land_high: Color::rgb(100, 230, 80),
+            seabed: Color::rgb(194, 178, 128),
         },
         &font,
     );
+    if let Some(gradient) = gradient_arg {
+        let result = if gradient.starts_with('#') {
+            Gradient::from_hex_stops(&gradient).map_err(|e| e.to_string())
+        } else {
+            Gradient::from_image(&gradient).map_err(|e| e.to_string())
+        };
+        match result {
+            Ok(gradient) => viewer.set_gradient(gradient),
+            Err(e) => println!("Could not load gradient {}: {}", gradient, e),
+        }
+    }
+
+    let (window_width, window_height) = graphics::drawable_size(&ctx);
+    viewer.resize(window_width, window_height);
     viewer.update_buffer();
 
     match event::run(&mut ctx, &mut event_loop, &mut viewer) {
@@ -71,3 +121,21 @@ fn main() -> GameResult {
 
     Ok(())
 }
+
+fn load_font(ctx: &mut Context, path: Option<&str>) -> Font {
+    if let Some(path) = path {
+        let font = std::fs::read(path)
+            .map_err(RealmsError::from)
+            .and_then(|bytes| Font::new_glyph_font_bytes(ctx, &bytes).map_err(RealmsError::Font));
+        match font {
+            Ok(font) => return font,
+            Err(e) => println!("{} ({}), using the embedded font", e, path),
+        }
+    }
+
+    // a broken font only costs the HUD its typeface, so fall back to ggez's built-in one
+    Font::new_glyph_font_bytes(ctx, include_bytes!("Px437_IBM_VGA_9x16.ttf")).unwrap_or_else(|e| {
+        println!("{}, using the default font", RealmsError::Font(e));
+        Font::default()
+    })
+}
@@ -0,0 +1,78 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const ONSETS: [&str; 16] = [
+    "b", "d", "f", "g", "k", "l", "m", "n", "r", "s", "t", "v", "th", "dr", "gr", "st",
+];
+const VOWELS: [&str; 8] = ["a", "e", "i", "o", "u", "ae", "ei", "ou"];
+const CODAS: [&str; 8] = ["", "", "n", "r", "l", "s", "th", "nd"];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FeatureKind {
+    Continent,
+    Peak,
+}
+
+impl FeatureKind {
+    // the fewest and most syllables, both inclusive
+    fn syllables(self) -> (usize, usize) {
+        match self {
+            FeatureKind::Continent => (2, 4),
+            FeatureKind::Peak => (1, 3),
+        }
+    }
+
+    fn syllable_count<R: Rng>(self, random: &mut R) -> usize {
+        let (min, max) = self.syllables();
+        random.gen_range(min, max + 1)
+    }
+
+    fn format(self, name: String) -> String {
+        match self {
+            FeatureKind::Continent => name,
+            FeatureKind::Peak => format!("Mount {}", name),
+        }
+    }
+}
+
+pub fn feature_name(seed: u64, kind: FeatureKind, x: usize, y: usize) -> String {
+    let feature_seed = seed
+        ^ (kind as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        ^ (x as u64).wrapping_mul(0xbf58_476d_1ce4_e5b9)
+        ^ (y as u64).wrapping_mul(0x94d0_49bb_1331_11eb);
+    let mut random = StdRng::seed_from_u64(feature_seed);
+
+    let name: String = (0..kind.syllable_count(&mut random))
+        .map(|_| {
+            format!(
+                "{}{}{}",
+                ONSETS[random.gen_range(0, ONSETS.len())],
+                VOWELS[random.gen_range(0, VOWELS.len())],
+                CODAS[random.gen_range(0, CODAS.len())],
+            )
+        })
+        .collect();
+
+    let mut chars = name.chars();
+    let name = match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => name,
+    };
+    kind.format(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn syllable_counts_reach_both_ends() {
+        for &kind in &[FeatureKind::Continent, FeatureKind::Peak] {
+            let mut random = StdRng::seed_from_u64(7);
+            let counts: Vec<usize> = (0..100).map(|_| kind.syllable_count(&mut random)).collect();
+            let (min, max) = kind.syllables();
+
+            assert_eq!(counts.iter().min(), Some(&min));
+            assert_eq!(counts.iter().max(), Some(&max));
+        }
+    }
+}
@@ -1,124 +1,1484 @@
-use noise::{NoiseFn, Perlin};
-use rand::{rngs::StdRng, RngCore, SeedableRng};
+use std::cmp::Ordering;
+use std::f64::consts::{FRAC_PI_2, PI};
+use std::hash::{Hash, Hasher};
+use std::ops::{Add, Mul, Sub};
 
-use crate::util::inverse_lerp;
+use noise::{NoiseFn, Perlin, Seedable};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+use crate::util::{float_bits, inverse_lerp, lerp};
+
+// Golden angle, so that no two octaves share an orientation.
+const OCTAVE_ROTATION: f64 = 2.399_963_229_728_653;
+const OCTAVE_OFFSET_RANGE: f64 = 65536.0;
+// With an integer lacunarity each octave's lattice refines the one before it, so wherever the
+// octave offsets bring two lattices into phase, the zero lines of Perlin noise stack up into
+// plus-shaped seams. A further shift by the golden ratio of a cell keeps octaves out of phase.
+const OCTAVE_PHASE_SHIFT: f64 = 0.618_033_988_749_895;
+
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct NoiseParameters {
-    pub scale: f64,
+    pub frequency: f64,
     pub octaves: usize,
     pub persistence: f64,
     pub lacunarity: f64,
+    pub rotate_octaves: bool,
+    pub preserve_aspect: bool,
+    pub antialias: bool,
+    pub wrap_x: bool,
+    pub decorrelate_octaves: bool,
+    pub domain_offset: (f64, f64),
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+impl NoiseParameters {
+    pub fn key(&self) -> impl Eq + Hash {
+        (
+            float_bits(self.frequency),
+            self.octaves,
+            float_bits(self.persistence),
+            float_bits(self.lacunarity),
+            self.rotate_octaves,
+            self.preserve_aspect,
+            self.antialias,
+            self.wrap_x,
+            self.decorrelate_octaves,
+            float_bits(self.domain_offset.0),
+            float_bits(self.domain_offset.1),
+        )
+    }
+
+    pub fn scale(&self) -> f64 {
+        1.0 / self.frequency
+    }
+
+    pub fn set_scale(&mut self, scale: f64) {
+        self.frequency = 1.0 / scale;
+    }
+
+    // integer fields round to the nearest value and flags switch over at t = 0.5
+    pub fn lerp(&self, other: &Self, t: f64) -> Self {
+        Self {
+            frequency: lerp(self.frequency, other.frequency, t),
+            octaves: lerp(self.octaves as f64, other.octaves as f64, t).round() as usize,
+            persistence: lerp(self.persistence, other.persistence, t),
+            lacunarity: lerp(self.lacunarity, other.lacunarity, t),
+            rotate_octaves: if t < 0.5 {
+                self.rotate_octaves
+            } else {
+                other.rotate_octaves
+            },
+            preserve_aspect: if t < 0.5 {
+                self.preserve_aspect
+            } else {
+                other.preserve_aspect
+            },
+            antialias: if t < 0.5 {
+                self.antialias
+            } else {
+                other.antialias
+            },
+            wrap_x: if t < 0.5 { self.wrap_x } else { other.wrap_x },
+            decorrelate_octaves: if t < 0.5 {
+                self.decorrelate_octaves
+            } else {
+                other.decorrelate_octaves
+            },
+            domain_offset: (
+                lerp(self.domain_offset.0, other.domain_offset.0, t),
+                lerp(self.domain_offset.1, other.domain_offset.1, t),
+            ),
+        }
+    }
+
+    // octaves past the Nyquist limit (half a cycle per pixel) only add sub-pixel detail
+    pub fn useful_octaves(&self, width: usize, height: usize) -> usize {
+        if self.lacunarity <= 1.0 {
+            return self.octaves;
+        }
+
+        let nyquist = width.max(height) as f64 / 2.0;
+        let mut frequency = self.frequency;
+        let mut octaves = 0;
+
+        while frequency <= nyquist && octaves < self.octaves {
+            frequency *= self.lacunarity;
+            octaves += 1;
+        }
+
+        octaves.max(1)
+    }
+}
+
+impl PartialEq for NoiseParameters {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for NoiseParameters {}
+
+impl Hash for NoiseParameters {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct FalloffParameters {
     pub a: f64,
     pub b: f64,
     pub multiplier: f64,
+    pub top: bool,
+    pub bottom: bool,
+    pub left: bool,
+    pub right: bool,
+}
+
+impl FalloffParameters {
+    pub fn key(&self) -> impl Eq + Hash {
+        (
+            float_bits(self.a),
+            float_bits(self.b),
+            float_bits(self.multiplier),
+            self.top,
+            self.bottom,
+            self.left,
+            self.right,
+        )
+    }
+
+    pub fn lerp(&self, other: &Self, t: f64) -> Self {
+        Self {
+            a: lerp(self.a, other.a, t),
+            b: lerp(self.b, other.b, t),
+            multiplier: lerp(self.multiplier, other.multiplier, t),
+            ..if t < 0.5 { *self } else { *other }
+        }
+    }
+}
+
+impl Default for FalloffParameters {
+    fn default() -> Self {
+        Self {
+            a: 2.0,
+            b: 6.0,
+            multiplier: 0.7,
+            top: true,
+            bottom: true,
+            left: true,
+            right: true,
+        }
+    }
+}
+
+impl PartialEq for FalloffParameters {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for FalloffParameters {}
+
+impl Hash for FalloffParameters {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+// band and transition are fractions of the map height, measured from the top and bottom edges
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PolarParameters {
+    pub band: f64,
+    pub transition: f64,
+    pub multiplier: f64,
+}
+
+impl PolarParameters {
+    pub fn key(&self) -> impl Eq + Hash {
+        (
+            float_bits(self.band),
+            float_bits(self.transition),
+            float_bits(self.multiplier),
+        )
+    }
+
+    pub fn lerp(&self, other: &Self, t: f64) -> Self {
+        Self {
+            band: lerp(self.band, other.band, t),
+            transition: lerp(self.transition, other.transition, t),
+            multiplier: lerp(self.multiplier, other.multiplier, t),
+        }
+    }
+}
+
+impl Default for PolarParameters {
+    fn default() -> Self {
+        Self {
+            band: 0.05,
+            transition: 0.1,
+            multiplier: 0.7,
+        }
+    }
+}
+
+impl PartialEq for PolarParameters {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for PolarParameters {}
+
+impl Hash for PolarParameters {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Connectivity {
+    #[default]
+    Four,
+    Eight,
 }
 
-pub struct NoiseMap {
-    pub map: Vec<f64>,
+impl Connectivity {
+    pub fn offsets(self) -> &'static [(isize, isize)] {
+        match self {
+            Connectivity::Four => &[(0, -1), (-1, 0), (1, 0), (0, 1)],
+            Connectivity::Eight => &[
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+                (-1, 0),
+                (1, 0),
+                (-1, 1),
+                (0, 1),
+                (1, 1),
+            ],
+        }
+    }
+}
+
+pub trait Float: Copy + PartialOrd + Into<f64> {
+    fn from_f64(value: f64) -> Self;
+}
+
+impl Float for f64 {
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+}
+
+impl Float for f32 {
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MapStats {
     pub min: f64,
     pub max: f64,
+    pub mean: f64,
+    pub stddev: f64,
+    pub median: f64,
+}
+
+#[derive(Clone)]
+pub struct NoiseMap<T: Float = f64> {
+    pub map: Vec<T>,
+    pub min: f64,
+    pub max: f64,
+    pub min_index: usize,
+    pub max_index: usize,
+    pub wrap_x: bool,
     width: usize,
     height: usize,
 }
 
-impl NoiseMap {
+impl<T: Float> NoiseMap<T> {
     pub fn normalize(&self, value: f64) -> f64 {
         inverse_lerp(self.min, self.max, value)
     }
 
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: f64) {
+        self.map[y * self.width + x] = T::from_f64(value);
+    }
+
     pub fn get(&self, x: usize, y: usize) -> f64 {
-        self.map[y * self.width + x]
+        self.map[y * self.width + x].into()
     }
 
     pub fn get_normalized(&self, x: usize, y: usize) -> f64 {
-        self.normalize(self.map[y * self.width + x])
+        self.normalize(self.get(x, y))
+    }
+
+    pub fn to_grayscale_bytes(&self) -> Vec<u8> {
+        self.map
+            .iter()
+            .map(|&value| (self.normalize(value.into()) * 255.0) as u8)
+            .collect()
+    }
+
+    pub fn to_rgba_bytes(&self) -> Vec<u8> {
+        self.to_grayscale_bytes()
+            .into_iter()
+            .flat_map(|gray| vec![gray, gray, gray, 255])
+            .collect()
+    }
+
+    pub fn hillshade(&self, azimuth: f64, altitude: f64, vertical_scale: f64) -> Vec<f64> {
+        (0..self.height)
+            .flat_map(|y| self.hillshade_row(y, azimuth, altitude, vertical_scale))
+            .collect()
+    }
+
+    // one row at a time, so that shading can be spread over several frames
+    pub fn hillshade_row(
+        &self,
+        y: usize,
+        azimuth: f64,
+        altitude: f64,
+        vertical_scale: f64,
+    ) -> Vec<f64> {
+        self.hillshade_span(y, 0, self.width, azimuth, altitude, vertical_scale)
+    }
+
+    // the columns x0..x1 of a row, for reshading around an edit
+    pub fn hillshade_span(
+        &self,
+        y: usize,
+        x0: usize,
+        x1: usize,
+        azimuth: f64,
+        altitude: f64,
+        vertical_scale: f64,
+    ) -> Vec<f64> {
+        let light = (
+            azimuth.sin() * altitude.cos(),
+            -azimuth.cos() * altitude.cos(),
+            altitude.sin(),
+        );
+
+        (x0..x1)
+            .map(|x| {
+                // central differences, one-sided where the map ends
+                let slope = |dx: isize, dy: isize| {
+                    let before = self.neighbor(x, y, -dx, -dy);
+                    let after = self.neighbor(x, y, dx, dy);
+                    let span = before.is_some() as usize + after.is_some() as usize;
+                    let (before, after) = (before.unwrap_or((x, y)), after.unwrap_or((x, y)));
+
+                    (self.get_normalized(after.0, after.1)
+                        - self.get_normalized(before.0, before.1))
+                        / span.max(1) as f64
+                        * vertical_scale
+                };
+                let (dx, dy) = (slope(1, 0), slope(0, 1));
+
+                let length = (dx * dx + dy * dy + 1.0).sqrt();
+                ((-dx * light.0 - dy * light.1 + light.2) / length).max(0.0)
+            })
+            .collect()
+    }
+
+    pub fn ambient_occlusion(&self, radius: usize, vertical_scale: f64) -> Vec<f64> {
+        (0..self.height)
+            .flat_map(|y| self.ambient_occlusion_row(y, radius, vertical_scale))
+            .collect()
+    }
+
+    pub fn ambient_occlusion_row(&self, y: usize, radius: usize, vertical_scale: f64) -> Vec<f64> {
+        self.ambient_occlusion_span(y, 0, self.width, radius, vertical_scale)
+    }
+
+    pub fn ambient_occlusion_span(
+        &self,
+        y: usize,
+        x0: usize,
+        x1: usize,
+        radius: usize,
+        vertical_scale: f64,
+    ) -> Vec<f64> {
+        let directions = Connectivity::Eight.offsets();
+
+        (x0..x1)
+            .map(|x| {
+                let height = self.get_normalized(x, y);
+
+                let occlusion: f64 = directions
+                    .iter()
+                    .map(|&(dx, dy)| {
+                        let step = ((dx * dx + dy * dy) as f64).sqrt();
+                        let horizon = (1..=radius)
+                            .filter_map(|d| {
+                                let (nx, ny) =
+                                    self.neighbor(x, y, dx * d as isize, dy * d as isize)?;
+                                let rise = (self.get_normalized(nx, ny) - height) * vertical_scale;
+                                Some(rise * radius as f64 / (d as f64 * step))
+                            })
+                            .fold(0.0, f64::max);
+
+                        horizon.atan() / FRAC_PI_2
+                    })
+                    .sum();
+
+                1.0 - occlusion / directions.len() as f64
+            })
+            .collect()
+    }
+
+    // min, max, mean and deviation come from a single Welford pass, the median from a selection
+    pub fn stats(&self) -> MapStats {
+        let mut min = f64::INFINITY;
+        let mut max = f64::NEG_INFINITY;
+        let mut mean = 0.0;
+        let mut squares = 0.0;
+
+        for (i, &value) in self.map.iter().enumerate() {
+            let value: f64 = value.into();
+            min = min.min(value);
+            max = max.max(value);
+
+            let delta = value - mean;
+            mean += delta / (i + 1) as f64;
+            squares += delta * (value - mean);
+        }
+
+        let mut values: Vec<f64> = self.map.iter().map(|&value| value.into()).collect();
+        let median = if values.is_empty() {
+            0.0
+        } else {
+            let middle = values.len() / 2;
+            let (lower, &mut upper, _) = values
+                .select_nth_unstable_by(middle, |a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+            if self.map.len() % 2 == 1 {
+                upper
+            } else {
+                (lower.iter().copied().fold(f64::NEG_INFINITY, f64::max) + upper) / 2.0
+            }
+        };
+
+        MapStats {
+            min,
+            max,
+            mean,
+            stddev: (squares / self.map.len().max(1) as f64).sqrt(),
+            median,
+        }
+    }
+
+    // the value below which `percent` percent of the cells lie
+    pub fn percentile(&self, percent: f64) -> f64 {
+        if self.map.is_empty() {
+            return 0.0;
+        }
+
+        let mut values: Vec<f64> = self.map.iter().map(|&value| value.into()).collect();
+        let rank = (percent / 100.0).clamp(0.0, 1.0) * (values.len() - 1) as f64;
+        let (_, &mut value, _) = values.select_nth_unstable_by(rank.round() as usize, |a, b| {
+            a.partial_cmp(b).unwrap_or(Ordering::Equal)
+        });
+        value
+    }
+
+    pub fn dominant_wavelength(&self) -> f64 {
+        const STRIDE: usize = 4;
+
+        let MapStats { mean, stddev, .. } = self.stats();
+        if stddev == 0.0 {
+            return 0.0;
+        }
+
+        // A sinusoid's autocorrelation first reaches zero at a quarter of its wavelength.
+        let max_lag = self.width.min(self.height) / 2;
+        for lag in 1..max_lag {
+            let mut covariance = 0.0;
+            let mut samples = 0;
+
+            for y in (0..self.height - lag).step_by(STRIDE) {
+                for x in (0..self.width - lag).step_by(STRIDE) {
+                    let value = self.get(x, y) - mean;
+                    covariance += value * (self.get(x + lag, y) - mean);
+                    covariance += value * (self.get(x, y + lag) - mean);
+                    samples += 2;
+                }
+            }
+
+            if covariance / samples as f64 <= 0.0 {
+                return 4.0 * lag as f64;
+            }
+        }
+
+        4.0 * max_lag as f64
+    }
+
+    pub fn neighbors(
+        &self,
+        x: usize,
+        y: usize,
+        connectivity: Connectivity,
+    ) -> impl Iterator<Item = (usize, usize)> + '_ {
+        connectivity
+            .offsets()
+            .iter()
+            .filter_map(move |&(dx, dy)| self.neighbor(x, y, dx, dy))
+    }
+
+    // the cell at an offset, across the seam when x wraps and None past the edges
+    pub fn neighbor(&self, x: usize, y: usize, dx: isize, dy: isize) -> Option<(usize, usize)> {
+        let ny = y as isize + dy;
+        if ny < 0 || ny >= self.height as isize {
+            return None;
+        }
+
+        let nx = x as isize + dx;
+        let nx = if self.wrap_x {
+            nx.rem_euclid(self.width as isize)
+        } else if nx < 0 || nx >= self.width as isize {
+            return None;
+        } else {
+            nx
+        };
+
+        Some((nx as usize, ny as usize))
+    }
+
+    // straight-line distance between two cells, going the short way around when x wraps
+    pub fn distance(&self, a: (usize, usize), b: (usize, usize)) -> f64 {
+        let dx = (a.0 as f64 - b.0 as f64).abs();
+        let dx = if self.wrap_x {
+            dx.min(self.width as f64 - dx)
+        } else {
+            dx
+        };
+        let dy = a.1 as f64 - b.1 as f64;
+        (dx * dx + dy * dy).sqrt()
+    }
+
+    pub fn threshold(&self, level: f64) -> Vec<bool> {
+        self.map
+            .iter()
+            .map(|&value| value.into() >= level)
+            .collect()
+    }
+
+    pub fn connected_components(&self, level: f64, connectivity: Connectivity) -> Vec<usize> {
+        let mut labels = vec![usize::MAX; self.map.len()];
+        let mut label = 0;
+        let mut stack = vec![];
+
+        for start in 0..self.map.len() {
+            if labels[start] != usize::MAX {
+                continue;
+            }
+
+            let above = self.map[start].into() >= level;
+            labels[start] = label;
+            stack.push(start);
+
+            while let Some(i) = stack.pop() {
+                for (nx, ny) in self.neighbors(i % self.width, i / self.width, connectivity) {
+                    let j = ny * self.width + nx;
+                    if labels[j] == usize::MAX && (self.map[j].into() >= level) == above {
+                        labels[j] = label;
+                        stack.push(j);
+                    }
+                }
+            }
+
+            label += 1;
+        }
+
+        labels
+    }
+
+    pub fn peaks(&self, min_prominence: f64) -> Vec<(usize, usize, f64)> {
+        let len = self.map.len();
+        let value = |i: usize| -> f64 { self.map[i].into() };
+
+        let mut order: Vec<usize> = (0..len).collect();
+        order.sort_by(|&a, &b| {
+            value(b)
+                .partial_cmp(&value(a))
+                .unwrap_or(Ordering::Equal)
+                .then(a.cmp(&b))
+        });
+
+        let mut rank = vec![0; len];
+        for (r, &i) in order.iter().enumerate() {
+            rank[i] = r;
+        }
+
+        // Cells are added from highest to lowest; whenever two components meet, the one with the
+        // lower summit ends there and its prominence is the drop from its summit to this saddle.
+        let mut parent = vec![usize::MAX; len];
+        let mut summit = vec![0; len];
+        let mut peaks = vec![];
+
+        for &i in &order {
+            parent[i] = i;
+            summit[i] = i;
+
+            for (nx, ny) in self.neighbors(i % self.width, i / self.width, Connectivity::Eight) {
+                let j = ny * self.width + nx;
+                if parent[j] == usize::MAX {
+                    continue;
+                }
+
+                let (a, b) = (Self::find(&mut parent, i), Self::find(&mut parent, j));
+                if a == b {
+                    continue;
+                }
+
+                let (high, low) = if rank[summit[a]] < rank[summit[b]] {
+                    (a, b)
+                } else {
+                    (b, a)
+                };
+
+                let prominence = value(summit[low]) - value(i);
+                if prominence > 0.0 && prominence >= min_prominence {
+                    let peak = summit[low];
+                    peaks.push((peak % self.width, peak / self.width, value(peak)));
+                }
+
+                parent[low] = high;
+            }
+        }
+
+        if let (Some(&highest), Some(&lowest)) = (order.first(), order.last()) {
+            if value(highest) - value(lowest) >= min_prominence {
+                peaks.push((highest % self.width, highest / self.width, value(highest)));
+            }
+        }
+
+        peaks.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(Ordering::Equal));
+        peaks
+    }
+
+    fn find(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    pub fn sample(&self, x: f64, y: f64) -> f64 {
+        let x = x.max(0.0).min((self.width - 1) as f64);
+        let y = y.max(0.0).min((self.height - 1) as f64);
+
+        let x0 = x.floor() as usize;
+        let y0 = y.floor() as usize;
+        let x1 = (x0 + 1).min(self.width - 1);
+        let y1 = (y0 + 1).min(self.height - 1);
+
+        let tx = x - x0 as f64;
+        let ty = y - y0 as f64;
+
+        lerp(
+            lerp(self.get(x0, y0), self.get(x1, y0), tx),
+            lerp(self.get(x0, y1), self.get(x1, y1), tx),
+            ty,
+        )
+    }
+
+    pub fn converted<U: Float>(&self) -> NoiseMap<U> {
+        NoiseMap {
+            map: self
+                .map
+                .iter()
+                .map(|&value| U::from_f64(value.into()))
+                .collect(),
+            min: self.min,
+            max: self.max,
+            min_index: self.min_index,
+            max_index: self.max_index,
+            wrap_x: self.wrap_x,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    pub fn resampled(&self, width: usize, height: usize) -> Self {
+        let scale_x = self.width as f64 / width as f64;
+        let scale_y = self.height as f64 / height as f64;
+
+        let map = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                T::from_f64(self.sample(
+                    (x as f64 + 0.5) * scale_x - 0.5,
+                    (y as f64 + 0.5) * scale_y - 0.5,
+                ))
+            })
+            .collect();
+
+        let mut resampled = Self {
+            map,
+            min: 0.0,
+            max: 0.0,
+            min_index: 0,
+            max_index: 0,
+            wrap_x: self.wrap_x,
+            width,
+            height,
+        };
+        resampled.update_bounds();
+        resampled
+    }
+
+    pub fn map_values<F: Fn(f64) -> f64>(&mut self, operation: F) {
+        for value in &mut self.map {
+            *value = T::from_f64(operation((*value).into()));
+        }
+        self.update_bounds();
+    }
+
+    // min and max go stale when values are edited in place, as falloff and erosion do
+    pub fn update_bounds(&mut self) {
+        self.min = f64::INFINITY;
+        self.max = f64::NEG_INFINITY;
+
+        for (i, &value) in self.map.iter().enumerate() {
+            let value = value.into();
+            if value < self.min {
+                self.min = value;
+                self.min_index = i;
+            }
+            if value > self.max {
+                self.max = value;
+                self.max_index = i;
+            }
+        }
+    }
+
+    // after an edit confined to a region: only a rescan of the region, unless it held an extreme
+    pub fn update_bounds_in(&mut self, x0: usize, y0: usize, x1: usize, y1: usize) {
+        let (x1, y1) = (x1.min(self.width), y1.min(self.height));
+        let inside =
+            |i: usize| (x0..x1).contains(&(i % self.width)) && (y0..y1).contains(&(i / self.width));
+        if inside(self.min_index) || inside(self.max_index) {
+            self.update_bounds();
+            return;
+        }
+
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let i = y * self.width + x;
+                let value = self.map[i].into();
+                if value < self.min {
+                    self.min = value;
+                    self.min_index = i;
+                }
+                if value > self.max {
+                    self.max = value;
+                    self.max_index = i;
+                }
+            }
+        }
+    }
+
+    pub fn min(&self, other: &Self) -> Self {
+        self.combine(other, f64::min)
     }
 
-    pub(crate) fn new(
+    pub fn max(&self, other: &Self) -> Self {
+        self.combine(other, f64::max)
+    }
+
+    fn combine<F: Fn(f64, f64) -> f64>(&self, other: &Self, operation: F) -> Self {
+        assert!(
+            self.width == other.width && self.height == other.height,
+            "cannot combine a {}x{} map with a {}x{} map",
+            self.width,
+            self.height,
+            other.width,
+            other.height
+        );
+
+        let mut min = f64::INFINITY;
+        let mut max = f64::NEG_INFINITY;
+        let mut min_index = 0;
+        let mut max_index = 0;
+
+        let map = self
+            .map
+            .iter()
+            .zip(&other.map)
+            .enumerate()
+            .map(|(i, (&a, &b))| {
+                let value = operation(a.into(), b.into());
+                if value < min {
+                    min = value;
+                    min_index = i;
+                }
+                if value > max {
+                    max = value;
+                    max_index = i;
+                }
+                T::from_f64(value)
+            })
+            .collect();
+
+        Self {
+            map,
+            min,
+            max,
+            min_index,
+            max_index,
+            wrap_x: self.wrap_x,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    // The minimal entry point: fractal Perlin noise from a seed, without falloff or any of the
+    // world layers on top.
+    pub fn generate(seed: u64, width: usize, height: usize, parameters: &NoiseParameters) -> Self {
+        Self::new(seed, None, width, height, parameters)
+    }
+
+    // offset_seed places the octaves independently of the Perlin gradient field, which is
+    // seeded by seed; without it both come from seed
+    pub fn new(
         seed: u64,
+        offset_seed: Option<u64>,
         width: usize,
         height: usize,
         parameters: &NoiseParameters,
-    ) -> NoiseMap {
-        let mut random = StdRng::seed_from_u64(seed);
-        let perlin = Perlin::new();
+    ) -> Self {
+        Self::new_with_offsets(
+            seed,
+            width,
+            height,
+            parameters,
+            &NoiseMap::octave_offsets(offset_seed.unwrap_or(seed), parameters.octaves),
+        )
+    }
 
-        let mut map = Vec::with_capacity(width * height);
+    pub fn new_with_offsets(
+        seed: u64,
+        width: usize,
+        height: usize,
+        parameters: &NoiseParameters,
+        octave_offsets: &[(f64, f64)],
+    ) -> Self {
+        Self::generate_octaves(
+            seed,
+            width,
+            height,
+            parameters,
+            octave_offsets,
+            (0.0, 0.0),
+            None,
+        )
+    }
 
-        let octave_offsets: Vec<(u32, u32)> = (0..parameters.octaves)
-            .map(|_| (random.next_u32(), random.next_u32()))
-            .collect();
+    // Tile (tx, ty) of an endless grid of width x height maps: it samples the noise domain at its
+    // absolute position, so neighboring tiles share their edges exactly. Falloff and polar
+    // suppression are relative to a single map and would break the seams, so they are left out.
+    // With wrap_x each tile already wraps onto itself, so tiles in a row come out identical.
+    pub fn new_tile(
+        seed: u64,
+        width: usize,
+        height: usize,
+        parameters: &NoiseParameters,
+        (tx, ty): (i64, i64),
+    ) -> Self {
+        Self::generate_octaves(
+            seed,
+            width,
+            height,
+            parameters,
+            &NoiseMap::octave_offsets(seed, parameters.octaves),
+            (tx as f64, ty as f64),
+            None,
+        )
+    }
+
+    pub fn new_with_layers(
+        seed: u64,
+        width: usize,
+        height: usize,
+        parameters: &NoiseParameters,
+        octave_offsets: &[(f64, f64)],
+    ) -> (Self, Vec<Vec<f64>>) {
+        let mut layers = vec![Vec::with_capacity(width * height); octave_offsets.len()];
+        let map = Self::generate_octaves(
+            seed,
+            width,
+            height,
+            parameters,
+            octave_offsets,
+            (0.0, 0.0),
+            Some(&mut layers),
+        );
+        (map, layers)
+    }
+
+    // origin is in map sizes, so that (1, 0) is the map immediately to the right
+    fn generate_octaves(
+        seed: u64,
+        width: usize,
+        height: usize,
+        parameters: &NoiseParameters,
+        octave_offsets: &[(f64, f64)],
+        origin: (f64, f64),
+        mut layers: Option<&mut Vec<Vec<f64>>>,
+    ) -> Self {
+        let sampler = OctaveSampler::new(seed, width, height, parameters, octave_offsets.len());
 
+        let mut map = Vec::with_capacity(width * height);
         let mut min = f64::INFINITY;
         let mut max = f64::NEG_INFINITY;
+        let mut min_index = 0;
+        let mut max_index = 0;
 
         for y in 0..height {
             for x in 0..width {
-                let mut amplitude = 1.0;
-                let mut frequency = 1.0;
+                let position = sampler.position(x, y, origin);
                 let mut value = 0.0;
 
-                for (offset_x, offset_y) in &octave_offsets {
-                    let sample_x = frequency * (x as f64 - width as f64 / 2.0 + *offset_x as f64)
-                        / (parameters.scale * width as f64);
-                    let sample_y = frequency * (y as f64 - height as f64 / 2.0 + *offset_y as f64)
-                        / (parameters.scale * height as f64);
-
-                    let sample = perlin.get([sample_x, sample_y]);
-                    value += amplitude * sample;
-                    amplitude *= parameters.persistence;
-                    frequency *= parameters.lacunarity;
+                for (octave, &offset) in octave_offsets.iter().enumerate() {
+                    let sample = sampler.sample(octave, position, offset);
+                    value += sample;
+                    if let Some(layers) = &mut layers {
+                        layers[octave].push(sample);
+                    }
                 }
 
                 if value < min {
-                    min = value
+                    min = value;
+                    min_index = map.len();
                 }
                 if value > max {
-                    max = value
+                    max = value;
+                    max_index = map.len();
                 }
 
-                map.push(value);
+                map.push(T::from_f64(value));
             }
         }
 
-        NoiseMap {
+        Self {
             map,
             min,
             max,
+            min_index,
+            max_index,
+            wrap_x: parameters.wrap_x,
             width,
             height,
         }
     }
+
     pub fn new_with_falloff(
         seed: u64,
         width: usize,
         height: usize,
         parameters: &NoiseParameters,
         falloff: &FalloffParameters,
-    ) -> NoiseMap {
-        let mut map = Self::new(seed, width, height, parameters);
+    ) -> Self {
+        let mut map = Self::new(seed, None, width, height, parameters);
+        map.apply_falloff(falloff);
+        map
+    }
+
+    pub fn apply_falloff(&mut self, falloff: &FalloffParameters) {
+        let (width, height) = (self.width, self.height);
 
         for y in 0..height {
             for x in 0..width {
-                let i = (x as f64 / width as f64 * 2.0 - 1.0).abs();
-                let j = (y as f64 / height as f64 * 2.0 - 1.0).abs();
+                let i = (x as f64 + 0.5) / width as f64 * 2.0 - 1.0;
+                let j = (y as f64 + 0.5) / height as f64 * 2.0 - 1.0;
+
+                // disabled edges contribute nothing, so land can run off them
+                let i = if (i < 0.0 && falloff.left) || (i > 0.0 && falloff.right) {
+                    i.abs()
+                } else {
+                    0.0
+                };
+                let j = if (j < 0.0 && falloff.top) || (j > 0.0 && falloff.bottom) {
+                    j.abs()
+                } else {
+                    0.0
+                };
 
                 let value = f64::max(i, j);
 
                 let falloff_value = Self::falloff(value, falloff.a, falloff.b);
-                map.map[y * width + x] -= (map.max - map.min) * falloff.multiplier * falloff_value;
+                let value =
+                    self.get(x, y) - (self.max - self.min) * falloff.multiplier * falloff_value;
+                self.map[y * width + x] = T::from_f64(value);
             }
         }
+    }
 
-        map
+    pub fn apply_polar_suppression(&mut self, polar: &PolarParameters) {
+        let (width, height) = (self.width, self.height);
+        let range = self.max - self.min;
+
+        for y in 0..height {
+            let pole_distance =
+                (y as f64 + 0.5).min(height as f64 - y as f64 - 0.5) / height as f64;
+            let t = if polar.transition > 0.0 {
+                inverse_lerp(polar.band + polar.transition, polar.band, pole_distance)
+            } else if pole_distance <= polar.band {
+                1.0
+            } else {
+                0.0
+            };
+            let t = t.clamp(0.0, 1.0);
+            let suppression = range * polar.multiplier * t * t * (3.0 - 2.0 * t);
+
+            for x in 0..width {
+                let value = self.get(x, y) - suppression;
+                self.map[y * width + x] = T::from_f64(value);
+            }
+        }
     }
 
     fn falloff(value: f64, a: f64, b: f64) -> f64 {
         value.powf(a) / (value.powf(a) + (b - b * value).powf(a))
     }
 }
+
+impl NoiseMap {
+    pub fn octave_offsets(seed: u64, octaves: usize) -> Vec<(f64, f64)> {
+        let mut random = StdRng::seed_from_u64(seed);
+
+        (0..octaves)
+            .map(|_| {
+                (
+                    random.gen_range(0.0, OCTAVE_OFFSET_RANGE),
+                    random.gen_range(0.0, OCTAVE_OFFSET_RANGE),
+                )
+            })
+            .collect()
+    }
+}
+
+struct OctaveSampler {
+    parameters: NoiseParameters,
+    width: usize,
+    height: usize,
+    perlins: Vec<Perlin>,
+    rotations: Vec<(f64, f64)>,
+    weights: Vec<f64>,
+    amplitudes: Vec<f64>,
+    frequencies: Vec<f64>,
+    aspect: f64,
+    radius: f64,
+}
+
+impl OctaveSampler {
+    fn new(
+        seed: u64,
+        width: usize,
+        height: usize,
+        parameters: &NoiseParameters,
+        octaves: usize,
+    ) -> Self {
+        // each octave gets its own gradient field, so octaves and seeds never share a lattice
+        let perlins = (0..octaves)
+            .map(|octave| Perlin::new().set_seed(perlin_seed(seed, octave)))
+            .collect();
+
+        let rotations = (0..octaves)
+            .map(|octave| {
+                if parameters.rotate_octaves {
+                    (octave as f64 * OCTAVE_ROTATION).sin_cos()
+                } else {
+                    (0.0, 1.0)
+                }
+            })
+            .collect();
+
+        let mut amplitudes = Vec::with_capacity(octaves);
+        let mut frequencies = Vec::with_capacity(octaves);
+        let (mut amplitude, mut frequency) = (1.0, 1.0);
+        for _ in 0..octaves {
+            amplitudes.push(amplitude);
+            frequencies.push(frequency);
+            amplitude *= parameters.persistence;
+            frequency *= parameters.lacunarity;
+        }
+
+        // fade out octaves between a quarter and half a cycle per pixel instead of letting them alias
+        let nyquist = width.max(height) as f64 / 2.0;
+        let weights = frequencies
+            .iter()
+            .map(|&frequency| {
+                if parameters.antialias {
+                    ((nyquist - parameters.frequency * frequency) / (nyquist / 2.0)).clamp(0.0, 1.0)
+                } else {
+                    1.0
+                }
+            })
+            .collect();
+
+        let aspect = if parameters.preserve_aspect {
+            height as f64 / width as f64
+        } else {
+            1.0
+        };
+
+        Self {
+            parameters: *parameters,
+            width,
+            height,
+            perlins,
+            rotations,
+            weights,
+            amplitudes,
+            frequencies,
+            aspect,
+            // wrapping samples a cylinder whose circumference matches the width of the flat plane
+            radius: parameters.frequency / (2.0 * PI),
+        }
+    }
+
+    fn position(&self, x: usize, y: usize, origin: (f64, f64)) -> (f64, f64, f64) {
+        let parameters = &self.parameters;
+        let u = origin.0 + (x as f64 + 0.5) / self.width as f64;
+        let v = origin.1 + (y as f64 + 0.5) / self.height as f64;
+
+        let (position_x, position_z) = if parameters.wrap_x {
+            let (sin, cos) = (2.0 * PI * u).sin_cos();
+            (self.radius * cos, self.radius * sin)
+        } else {
+            (parameters.frequency * (u - 0.5), 0.0)
+        };
+        let position_y = parameters.frequency * (v - 0.5) * self.aspect;
+
+        (position_x, position_y, position_z)
+    }
+
+    // the octave's contribution to the sum, already scaled by its amplitude
+    fn sample(
+        &self,
+        octave: usize,
+        (position_x, position_y, position_z): (f64, f64, f64),
+        (offset_x, offset_y): (f64, f64),
+    ) -> f64 {
+        let parameters = &self.parameters;
+        let frequency = self.frequencies[octave];
+        let phase = if parameters.decorrelate_octaves {
+            (octave as f64 * OCTAVE_PHASE_SHIFT).fract()
+        } else {
+            0.0
+        };
+        // the domain offset is in noise units at the base frequency, frequency / width per cell,
+        // so that all octaves move together
+        let sample_x = frequency * (position_x + parameters.domain_offset.0 + offset_x) + phase;
+        let sample_y = frequency * (position_y + parameters.domain_offset.1 + offset_y) + phase;
+
+        let (sin, cos) = self.rotations[octave];
+        let rotated_x = sample_x * cos - sample_y * sin;
+        let rotated_y = sample_x * sin + sample_y * cos;
+        let sample = if parameters.wrap_x {
+            let sample_z = frequency * (position_z + offset_x);
+            self.perlins[octave].get([rotated_x, rotated_y, sample_z])
+        } else {
+            self.perlins[octave].get([rotated_x, rotated_y])
+        };
+
+        self.amplitudes[octave] * sample * self.weights[octave]
+    }
+}
+
+// The raw octave sum before falloff, kept so that octaves can be added or removed one at a time
+// instead of summing all of them again.
+#[derive(Clone)]
+pub struct OctaveSum {
+    seed: u64,
+    width: usize,
+    height: usize,
+    parameters: NoiseParameters,
+    octave_offsets: Vec<(f64, f64)>,
+    sum: Vec<f64>,
+}
+
+impl OctaveSum {
+    pub fn new(
+        seed: u64,
+        width: usize,
+        height: usize,
+        parameters: &NoiseParameters,
+        octave_offsets: &[(f64, f64)],
+    ) -> Self {
+        let sum = NoiseMap::new_with_offsets(seed, width, height, parameters, octave_offsets);
+        Self::from_map(seed, parameters, octave_offsets, sum)
+    }
+
+    // for a map that new_with_offsets already generated from these octaves
+    pub fn from_map(
+        seed: u64,
+        parameters: &NoiseParameters,
+        octave_offsets: &[(f64, f64)],
+        map: NoiseMap,
+    ) -> Self {
+        Self {
+            seed,
+            width: map.width,
+            height: map.height,
+            parameters: *parameters,
+            octave_offsets: octave_offsets.to_vec(),
+            sum: map.map,
+        }
+    }
+
+    pub fn octave_offsets(&self) -> &[(f64, f64)] {
+        &self.octave_offsets
+    }
+
+    pub fn add_octave(&mut self, offset: (f64, f64)) {
+        self.accumulate(self.octave_offsets.len(), offset, 1.0);
+        self.octave_offsets.push(offset);
+    }
+
+    pub fn remove_octave(&mut self) {
+        if let Some(offset) = self.octave_offsets.pop() {
+            self.accumulate(self.octave_offsets.len(), offset, -1.0);
+        }
+    }
+
+    fn accumulate(&mut self, octave: usize, offset: (f64, f64), sign: f64) {
+        let sampler = OctaveSampler::new(
+            self.seed,
+            self.width,
+            self.height,
+            &self.parameters,
+            octave + 1,
+        );
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let position = sampler.position(x, y, (0.0, 0.0));
+                self.sum[y * self.width + x] += sign * sampler.sample(octave, position, offset);
+            }
+        }
+    }
+
+    pub fn to_map<T: Float>(&self) -> NoiseMap<T> {
+        let mut map = NoiseMap {
+            map: self.sum.iter().map(|&value| T::from_f64(value)).collect(),
+            min: 0.0,
+            max: 0.0,
+            min_index: 0,
+            max_index: 0,
+            wrap_x: self.parameters.wrap_x,
+            width: self.width,
+            height: self.height,
+        };
+        map.update_bounds();
+        map
+    }
+}
+
+impl<T: Float> Add for &NoiseMap<T> {
+    type Output = NoiseMap<T>;
+
+    fn add(self, other: Self) -> NoiseMap<T> {
+        self.combine(other, |a, b| a + b)
+    }
+}
+
+impl<T: Float> Sub for &NoiseMap<T> {
+    type Output = NoiseMap<T>;
+
+    fn sub(self, other: Self) -> NoiseMap<T> {
+        self.combine(other, |a, b| a - b)
+    }
+}
+
+impl<T: Float> Mul for &NoiseMap<T> {
+    type Output = NoiseMap<T>;
+
+    fn mul(self, other: Self) -> NoiseMap<T> {
+        self.combine(other, |a, b| a * b)
+    }
+}
+
+// Mixes the whole 64-bit seed, so that neither seeds differing only in their high bits nor
+// neighboring seeds end up reusing each other's gradient fields.
+fn perlin_seed(seed: u64, octave: usize) -> u32 {
+    let mut z = seed.wrapping_add((octave as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (z ^ (z >> 31)) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parameters() -> NoiseParameters {
+        NoiseParameters {
+            frequency: 4.0,
+            octaves: 4,
+            persistence: 0.5,
+            lacunarity: 2.0,
+            rotate_octaves: false,
+            preserve_aspect: true,
+            antialias: false,
+            wrap_x: false,
+            decorrelate_octaves: false,
+            domain_offset: (0.0, 0.0),
+        }
+    }
+
+    #[test]
+    fn seeds_change_the_map_with_the_same_offsets() {
+        let parameters = parameters();
+        let offsets = NoiseMap::octave_offsets(7, parameters.octaves);
+
+        let a = NoiseMap::<f64>::new_with_offsets(1, 32, 32, &parameters, &offsets);
+        let b = NoiseMap::<f64>::new_with_offsets(2, 32, 32, &parameters, &offsets);
+
+        assert_ne!(a.map, b.map);
+    }
+
+    #[test]
+    fn shading_spans_match_their_rows() {
+        let parameters = parameters();
+        let offsets = NoiseMap::octave_offsets(7, parameters.octaves);
+        let map = NoiseMap::<f64>::new_with_offsets(1, 32, 32, &parameters, &offsets);
+
+        for &y in &[0, 5, 31] {
+            assert_eq!(
+                map.hillshade_span(y, 3, 12, 1.0, 0.5, 4.0),
+                map.hillshade_row(y, 1.0, 0.5, 4.0)[3..12].to_vec()
+            );
+            assert_eq!(
+                map.ambient_occlusion_span(y, 3, 12, 8, 4.0),
+                map.ambient_occlusion_row(y, 8, 4.0)[3..12].to_vec()
+            );
+        }
+    }
+
+    fn blank(width: usize, height: usize, wrap_x: bool) -> NoiseMap {
+        NoiseMap {
+            map: vec![0.0; width * height],
+            min: 0.0,
+            max: 0.0,
+            min_index: 0,
+            max_index: 0,
+            wrap_x,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn wrap_x_hillshade_follows_the_map_across_the_seam() {
+        let (width, height) = (16, 4);
+        let mut map = blank(width, height, true);
+        let mut shifted = blank(width, height, true);
+        for y in 0..height {
+            for x in 0..width {
+                let value = (x as f64 / width as f64 * 2.0 * PI).sin() + y as f64 * 0.1;
+                map.set(x, y, value);
+                shifted.set((x + 1) % width, y, value);
+            }
+        }
+        map.update_bounds();
+        shifted.update_bounds();
+
+        let shade = map.hillshade(1.0, 0.8, 4.0);
+        let shifted_shade = shifted.hillshade(1.0, 0.8, 4.0);
+        for y in 0..height {
+            for x in 0..width {
+                let expected = shade[y * width + x];
+                let actual = shifted_shade[y * width + (x + 1) % width];
+                assert!((expected - actual).abs() < 1e-9, "{}, {}", x, y);
+            }
+        }
+    }
+
+    fn sorted_neighbors(
+        map: &NoiseMap,
+        x: usize,
+        y: usize,
+        connectivity: Connectivity,
+    ) -> Vec<(usize, usize)> {
+        let mut neighbors: Vec<_> = map.neighbors(x, y, connectivity).collect();
+        neighbors.sort();
+        neighbors
+    }
+
+    #[test]
+    fn corner_neighbors_stay_on_the_map() {
+        let map = blank(4, 3, false);
+
+        assert_eq!(
+            sorted_neighbors(&map, 0, 0, Connectivity::Four),
+            vec![(0, 1), (1, 0)]
+        );
+        assert_eq!(
+            sorted_neighbors(&map, 3, 2, Connectivity::Eight),
+            vec![(2, 1), (2, 2), (3, 1)]
+        );
+    }
+
+    #[test]
+    fn edge_neighbors_stay_on_the_map() {
+        let map = blank(4, 3, false);
+
+        assert_eq!(
+            sorted_neighbors(&map, 2, 0, Connectivity::Four),
+            vec![(1, 0), (2, 1), (3, 0)]
+        );
+        assert_eq!(sorted_neighbors(&map, 0, 1, Connectivity::Eight).len(), 5);
+    }
+
+    #[test]
+    fn interior_cells_have_every_neighbor() {
+        let map = blank(4, 3, false);
+
+        assert_eq!(
+            sorted_neighbors(&map, 1, 1, Connectivity::Four),
+            vec![(0, 1), (1, 0), (1, 2), (2, 1)]
+        );
+        assert_eq!(sorted_neighbors(&map, 1, 1, Connectivity::Eight).len(), 8);
+    }
+
+    #[test]
+    fn wrap_x_neighbors_cross_the_seam_but_not_the_poles() {
+        let map = blank(4, 3, true);
+
+        assert_eq!(
+            sorted_neighbors(&map, 0, 0, Connectivity::Four),
+            vec![(0, 1), (1, 0), (3, 0)]
+        );
+        assert_eq!(
+            sorted_neighbors(&map, 3, 1, Connectivity::Eight),
+            vec![
+                (0, 0),
+                (0, 1),
+                (0, 2),
+                (2, 0),
+                (2, 1),
+                (2, 2),
+                (3, 0),
+                (3, 2)
+            ]
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use crate::noisemap::{Connectivity, Float, NoiseMap};
+
+struct Node {
+    estimate: f64,
+    index: usize,
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Node {}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Reversed so that the BinaryHeap pops the lowest estimate first.
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .estimate
+            .partial_cmp(&self.estimate)
+            .unwrap_or(Ordering::Equal)
+            .then(other.index.cmp(&self.index))
+    }
+}
+
+// The heuristic is the straight-line distance, so `cost` must never return less than the
+// distance between the two cells for the path to be optimal. None marks a step as impassable.
+pub fn find_path<T, F>(
+    map: &NoiseMap<T>,
+    start: (usize, usize),
+    goal: (usize, usize),
+    cost: F,
+) -> Option<Vec<(usize, usize)>>
+where
+    T: Float,
+    F: Fn((usize, usize), (usize, usize)) -> Option<f64>,
+{
+    let width = map.width();
+    let heuristic = |cell: (usize, usize)| map.distance(cell, goal);
+
+    let mut distances = vec![f64::INFINITY; width * map.height()];
+    let mut previous = vec![usize::MAX; width * map.height()];
+    let mut open = BinaryHeap::new();
+
+    let start_index = start.1 * width + start.0;
+    let goal_index = goal.1 * width + goal.0;
+    distances[start_index] = 0.0;
+    open.push(Node {
+        estimate: heuristic(start),
+        index: start_index,
+    });
+
+    while let Some(Node { estimate, index }) = open.pop() {
+        if index == goal_index {
+            let mut path = vec![goal];
+            let mut current = goal_index;
+            while current != start_index {
+                current = previous[current];
+                path.push((current % width, current / width));
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        let cell = (index % width, index / width);
+        if estimate > distances[index] + heuristic(cell) {
+            continue;
+        }
+
+        for neighbor in map.neighbors(cell.0, cell.1, Connectivity::Eight) {
+            let step = match cost(cell, neighbor) {
+                Some(step) => step,
+                None => continue,
+            };
+
+            let neighbor_index = neighbor.1 * width + neighbor.0;
+            let distance = distances[index] + step;
+            if distance < distances[neighbor_index] {
+                distances[neighbor_index] = distance;
+                previous[neighbor_index] = index;
+                open.push(Node {
+                    estimate: distance + heuristic(neighbor),
+                    index: neighbor_index,
+                });
+            }
+        }
+    }
+
+    None
+}
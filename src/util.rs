@@ -1,3 +1,7 @@
+use std::f64::consts::PI;
+
+use rand::Rng;
+
 pub fn lerp(a: f64, b: f64, t: f64) -> f64 {
     a + (b - a) * t
 }
@@ -5,3 +9,42 @@ pub fn lerp(a: f64, b: f64, t: f64) -> f64 {
 pub fn inverse_lerp(min: f64, max: f64, value: f64) -> f64 {
     (value - min) / (max - min)
 }
+
+pub fn srgb_to_linear(value: f64) -> f64 {
+    if value <= 0.040_45 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(value: f64) -> f64 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// NaN and -0.0 each collapse to a single bit pattern, so parameters can be compared and hashed
+// exactly
+pub fn float_bits(value: f64) -> u64 {
+    if value.is_nan() {
+        f64::NAN.to_bits()
+    } else if value == 0.0 {
+        0
+    } else {
+        value.to_bits()
+    }
+}
+
+pub fn snap(value: f64, step: f64) -> f64 {
+    let factor = 10f64.powi((-step.log10()).ceil().max(0.0) as i32);
+    (value * factor).round() / factor
+}
+
+pub fn gaussian<R: Rng + ?Sized>(random: &mut R) -> f64 {
+    let u: f64 = 1.0 - random.gen::<f64>();
+    let v: f64 = random.gen();
+    (-2.0 * u.ln()).sqrt() * (2.0 * PI * v).cos()
+}
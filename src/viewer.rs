@@ -1,227 +1,1754 @@
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use clipboard::{ClipboardContext, ClipboardProvider};
 use ggez::event::KeyCode;
 use ggez::graphics::{Font, TextFragment};
-use ggez::input::keyboard::KeyMods;
+use ggez::input::keyboard::{self, KeyMods};
+use ggez::input::mouse;
 use ggez::{
     event::{EventHandler, MouseButton},
     graphics::{self, DrawParam},
-    Context, GameResult,
+    timer, Context, GameResult,
 };
 use rand::{thread_rng, RngCore};
 
-use crate::util::{inverse_lerp, lerp};
-use crate::world::{World, WorldParameters};
+use realms::coloring::{
+    self, Color, ColorSpace, Colors, Gradient, Renderer, ViewMode, DEFAULT_SUN_ALTITUDE,
+    DEFAULT_SUN_AZIMUTH, HYPSOMETRIC_TINTS,
+};
+use realms::erosion::ErosionParameters;
+use realms::error::RealmsError;
+use realms::names::FeatureKind;
+use realms::noisemap::{Connectivity, FalloffParameters, MapStats, NoiseMap, PolarParameters};
+use realms::util::{inverse_lerp, lerp, snap};
+use realms::world::{SeaLevelMode, SvgOptions, World, WorldParameters};
+
+use crate::favorites::{self, Favorite};
+use crate::keybindings::{Action, KeyBindings};
+use crate::legend;
+
+const OCTAVE_THUMBNAIL_SIZE: f32 = 96.0;
+const OCTAVE_SATURATION: f64 = 0.5;
+const HUD_FONT_SIZE: f32 = 16.0;
+const HUD_REFERENCE_HEIGHT: f32 = 720.0;
+const AMBIENT_OCCLUSION_RADIUS: usize = 8;
+const SUN_SWEEP_SPEED: f64 = 30.0;
+const FLOOD_COLOR: Color = Color {
+    r: 220,
+    g: 60,
+    b: 40,
+    a: 153,
+};
+const HUD_BACKGROUND: graphics::Color = graphics::Color::new(0.0, 0.0, 0.0, 0.6);
+const HUD_PADDING: f32 = 4.0;
+const SELECTION_COLOR: Color = Color {
+    r: 255,
+    g: 230,
+    b: 80,
+    a: 120,
+};
+const WARNING_COLOR: graphics::Color = graphics::Color::new(1.0, 0.85, 0.2, 1.0);
+const VARIANT_AMOUNT: f64 = 0.02;
+const JUMP_ZOOM: f32 = 4.0;
+const STATUS_DURATION: Duration = Duration::from_secs(2);
+const SWEEP_FRAMES: usize = 20;
+const WORLD_CACHE_SIZE: usize = 8;
+const PREVIEW_DEBOUNCE: Duration = Duration::from_millis(300);
+const RENDER_BUDGET: Duration = Duration::from_millis(12);
+const EXPORT_RESAMPLE_FACTOR: u32 = 4;
+const LEGEND_WIDTH: usize = 112;
+const LEGEND_BAR_WIDTH: usize = 16;
+const LEGEND_BAR_HEIGHT: usize = 256;
+const LEGEND_MARGIN: usize = 8;
+const LEGEND_TICKS: usize = 5;
+const LEGEND_TEXT_SCALE: usize = 2;
+const FAVORITES_PATH: &str = "favorites.txt";
+const KEY_BINDINGS_PATH: &str = "keybindings.txt";
+const LABEL_PEAK_PROMINENCE: f64 = 0.15;
+const LABEL_MAX_PEAKS: usize = 12;
+const LABEL_MIN_LANDMASS: usize = 200;
+
+macro_rules! float_row {
+    ($t:expr, $($field:ident).+, $step:expr) => {
+        EditableRow {
+            text: Box::new(|parameters| format!($t, parameters.$($field).+)),
+            edit: Box::new(|parameters, action| {
+                let value = match action {
+                    EditType::Right => parameters.$($field).+ + $step,
+                    EditType::Left => parameters.$($field).+ - $step,
+                    _ => return,
+                };
+                parameters.$($field).+ = if parameters.snap {
+                    snap(value, $step)
+                } else {
+                    value
+                };
+            }),
+            reset: Box::new(|parameters, defaults| {
+                parameters.$($field).+ = defaults.$($field).+
+            }),
+        }
+    };
+}
 
 macro_rules! bool_row {
-    ($t:expr, $i:ident) => {
+    ($t:expr, $($field:ident).+) => {
         EditableRow {
-            text: Box::new(|parameters| format!($t, if parameters.$i { '■' } else { '□' },)),
+            text: Box::new(|parameters| {
+                format!($t, if parameters.$($field).+ { '■' } else { '□' },)
+            }),
             edit: Box::new(|parameters, action| {
                 if let EditType::Press = action {
-                    parameters.$i = !parameters.$i
+                    parameters.$($field).+ = !parameters.$($field).+
                 }
             }),
+            reset: Box::new(|parameters, defaults| {
+                parameters.$($field).+ = defaults.$($field).+
+            }),
         }
     };
 }
 
-pub struct Color {
-    pub r: u8,
-    pub g: u8,
-    pub b: u8,
-    pub a: u8,
+macro_rules! option_row {
+    ($option:ident, $label:expr, $field:ident, $step:expr) => {
+        EditableRow {
+            text: Box::new(|parameters| match &parameters.world.$option {
+                Some(option) => format!("{}: {:.2}", $label, option.$field),
+                None => format!("{}: off", $label),
+            }),
+            edit: Box::new(|parameters, action| {
+                let snap_values = parameters.snap;
+                if let Some(option) = &mut parameters.world.$option {
+                    let value = match action {
+                        EditType::Right => option.$field + $step,
+                        EditType::Left => option.$field - $step,
+                        _ => return,
+                    };
+                    option.$field = if snap_values {
+                        snap(value, $step)
+                    } else {
+                        value
+                    };
+                }
+            }),
+            reset: Box::new(|parameters, defaults| {
+                if let Some(option) = &mut parameters.world.$option {
+                    option.$field = defaults.world.$option.unwrap_or_default().$field;
+                }
+            }),
+        }
+    };
 }
 
-impl Color {
-    pub fn rgb(r: u8, g: u8, b: u8) -> Self {
-        Self { r, g, b, a: 255 }
-    }
-
-    pub fn into_vec(self) -> Vec<u8> {
-        vec![self.r, self.g, self.b, self.a]
-    }
+macro_rules! option_bool_row {
+    ($option:ident, $label:expr, $field:ident) => {
+        EditableRow {
+            text: Box::new(|parameters| match &parameters.world.$option {
+                Some(option) => format!("{}: {}", $label, if option.$field { '■' } else { '□' }),
+                None => format!("{}: off", $label),
+            }),
+            edit: Box::new(|parameters, action| {
+                if let (EditType::Press, Some(option)) = (action, &mut parameters.world.$option) {
+                    option.$field = !option.$field;
+                }
+            }),
+            reset: Box::new(|parameters, defaults| {
+                if let Some(option) = &mut parameters.world.$option {
+                    option.$field = defaults.world.$option.unwrap_or_default().$field;
+                }
+            }),
+        }
+    };
 }
 
-impl Into<Vec<u8>> for Color {
-    fn into(self) -> Vec<u8> {
-        self.into_vec()
-    }
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Tool {
+    Pan,
+    Measure,
+    Path,
+    Select,
 }
 
-pub struct Colors {
-    pub sea_low: Color,
-    pub sea_high: Color,
-    pub land_low: Color,
-    pub land_high: Color,
+impl Tool {
+    const ALL: [Tool; 4] = [Tool::Pan, Tool::Measure, Tool::Path, Tool::Select];
+
+    fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&tool| tool == self).unwrap();
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
 }
 
+#[derive(Clone, PartialEq)]
 pub struct WorldViewerParameters {
     world: WorldParameters,
     auto_generate: bool,
+    preview_scale: f64,
     shuffle_seed: bool,
+    lock_seed: bool,
+    supersample: usize,
+    view_mode: ViewMode,
+    dither: bool,
+    color_space: ColorSpace,
+    show_octaves: bool,
+    show_labels: bool,
+    show_stats: bool,
+    selected_octave: usize,
+    snap: bool,
+    occlusion_strength: f64,
+    vertical_scale: f64,
+    water_depth_scale: f64,
+    sun_azimuth: f64,
+    sun_altitude: f64,
+    erosion: ErosionParameters,
 }
 
 pub struct WorldViewer<'f> {
-    world: World,
+    world: World<f32>,
+    world_cache: VecDeque<World<f32>>,
     colors: Colors,
+    gradient: Option<Gradient>,
+    hypsometric_tints: Gradient,
     buffer: Vec<u8>,
+    buffer_width: usize,
+    buffer_height: usize,
+    render_row: Option<usize>,
+    occluded_rows: usize,
+    hillshaded_rows: usize,
+    labels_pending: bool,
+    stats_pending: bool,
+    octave_buffers: Vec<Vec<u8>>,
+    ambient_occlusion: Vec<f64>,
+    hillshade: Vec<f64>,
+    stats: Option<MapStats>,
     scale: f32,
     offset: [f32; 2],
+    window_size: [f32; 2],
+    user_zoomed: bool,
     mouse_down: bool,
     last_mouse_x: f32,
     last_mouse_y: f32,
     font: &'f Font,
+    hud_scale: f32,
+    hud_bounds: graphics::Rect,
     parameters: WorldViewerParameters,
+    defaults: WorldViewerParameters,
+    current_group: usize,
     current_row: usize,
-    rows: Vec<EditableRow>,
+    groups: Vec<RowGroup>,
+    status: Option<(String, Instant)>,
+    flood_preview: bool,
+    flood_delta: f64,
+    erosion_stepping: bool,
+    erosion_world: Option<World<f64>>,
+    sweep: Option<Sweep>,
+    last_edit: Option<Instant>,
+    preview_pending: bool,
+    labels: Vec<Label>,
+    favorites: Vec<Favorite>,
+    favorite_index: usize,
+    key_bindings: KeyBindings,
+    show_help: bool,
+    tool: Tool,
+    sun_sweeping: bool,
+    measure_points: Vec<(usize, usize)>,
+    path: Option<Vec<(usize, usize)>>,
+    selection: Vec<bool>,
+    selection_size: usize,
 }
 
 impl<'f> WorldViewer<'f> {
-    pub fn new(world: World, colors: Colors, font: &'f Font) -> Self {
+    pub fn new(world: World<f32>, colors: Colors, font: &'f Font) -> Self {
+        let parameters = WorldViewerParameters {
+            world: world.parameters,
+            auto_generate: true,
+            preview_scale: 0.25,
+            shuffle_seed: true,
+            lock_seed: false,
+            supersample: 1,
+            view_mode: ViewMode::Terrain,
+            dither: false,
+            color_space: ColorSpace::Rgb,
+            show_octaves: false,
+            show_labels: false,
+            show_stats: false,
+            selected_octave: 0,
+            snap: true,
+            occlusion_strength: 0.0,
+            vertical_scale: 1.0,
+            water_depth_scale: 0.0,
+            sun_azimuth: DEFAULT_SUN_AZIMUTH,
+            sun_altitude: DEFAULT_SUN_ALTITUDE,
+            erosion: ErosionParameters::default(),
+        };
+
         Self {
-            scale: 1000.0 / world.parameters.width as f32,
+            scale: 1.0,
             colors,
+            gradient: None,
+            hypsometric_tints: Gradient::new(HYPSOMETRIC_TINTS.to_vec()),
             font,
+            hud_scale: HUD_FONT_SIZE,
+            hud_bounds: graphics::Rect::new(0.0, 0.0, 0.0, 0.0),
             buffer: vec![],
+            buffer_width: 0,
+            buffer_height: 0,
+            render_row: None,
+            occluded_rows: 0,
+            hillshaded_rows: 0,
+            labels_pending: false,
+            stats_pending: false,
+            octave_buffers: vec![],
+            ambient_occlusion: vec![],
+            hillshade: vec![],
+            stats: None,
             offset: [0.0, 0.0],
+            window_size: [0.0, 0.0],
+            user_zoomed: false,
             mouse_down: false,
             last_mouse_x: 0.0,
             last_mouse_y: 0.0,
-            parameters: WorldViewerParameters {
-                world: world.parameters,
-                auto_generate: true,
-                shuffle_seed: true,
-            },
+            defaults: parameters.clone(),
+            parameters,
             world,
+            world_cache: VecDeque::new(),
+            current_group: 0,
             current_row: 0,
-            rows: vec![
-                EditableRow {
-                    text: Box::new(|parameters| {
-                        format!("sea level: {:.2}", parameters.world.sea_level)
-                    }),
-                    edit: Box::new(|parameters, action| match action {
-                        EditType::Right => parameters.world.sea_level += 0.1,
-                        EditType::Left => parameters.world.sea_level -= 0.1,
-                        _ => {}
-                    }),
-                },
-                EditableRow {
-                    text: Box::new(|parameters| {
-                        format!("octaves: {}", parameters.world.elevation_parameters.octaves)
-                    }),
-                    edit: Box::new(|parameters, action| match action {
-                        EditType::Right => parameters.world.elevation_parameters.octaves += 1,
-                        EditType::Left => parameters.world.elevation_parameters.octaves -= 1,
-                        _ => {}
-                    }),
-                },
-                EditableRow {
-                    text: Box::new(|parameters| {
-                        format!(
+            status: None,
+            flood_preview: false,
+            flood_delta: 0.1,
+            erosion_stepping: false,
+            erosion_world: None,
+            sweep: None,
+            last_edit: None,
+            preview_pending: false,
+            labels: vec![],
+            favorites: favorites::load(FAVORITES_PATH).unwrap_or_else(|e| {
+                println!("Could not load favorites: {}", e);
+                vec![]
+            }),
+            favorite_index: 0,
+            key_bindings: KeyBindings::load(KEY_BINDINGS_PATH).unwrap_or_else(|e| {
+                println!("Could not load key bindings: {}", e);
+                KeyBindings::default()
+            }),
+            show_help: false,
+            tool: Tool::Pan,
+            sun_sweeping: false,
+            measure_points: vec![],
+            path: None,
+            selection: vec![],
+            selection_size: 0,
+            groups: vec![
+                RowGroup {
+                    name: "Elevation",
+                    rows: vec![
+                        EditableRow {
+                            text: Box::new(|parameters| match parameters.world.sea_level {
+                                SeaLevelMode::Absolute(level) => {
+                                    format!("sea level: {:.2}", level)
+                                }
+                                SeaLevelMode::Percentile(percent) => {
+                                    format!("sea level: {:.0}% flooded", percent)
+                                }
+                            }),
+                            edit: Box::new(|parameters, action| {
+                                let (value, step) = match &mut parameters.world.sea_level {
+                                    SeaLevelMode::Absolute(level) => (level, 0.1),
+                                    SeaLevelMode::Percentile(percent) => (percent, 5.0),
+                                };
+                                match action {
+                                    EditType::Right => *value += step,
+                                    EditType::Left => *value -= step,
+                                    EditType::Press => {
+                                        parameters.world.sea_level =
+                                            match parameters.world.sea_level {
+                                                SeaLevelMode::Absolute(_) => {
+                                                    SeaLevelMode::Percentile(50.0)
+                                                }
+                                                SeaLevelMode::Percentile(_) => {
+                                                    SeaLevelMode::Absolute(0.0)
+                                                }
+                                            };
+                                        return;
+                                    }
+                                }
+                                if parameters.snap {
+                                    *value = snap(*value, step);
+                                }
+                            }),
+                            reset: Box::new(|parameters, defaults| {
+                                parameters.world.sea_level = defaults.world.sea_level
+                            }),
+                        },
+                        EditableRow {
+                            text: Box::new(|parameters| {
+                                let world = &parameters.world;
+                                let octaves = world.elevation_parameters.octaves;
+                                let useful = world
+                                    .elevation_parameters
+                                    .useful_octaves(world.width, world.height);
+
+                                if useful < octaves {
+                                    format!("octaves: {} ({} useful)", octaves, useful)
+                                } else {
+                                    format!("octaves: {}", octaves)
+                                }
+                            }),
+                            edit: Box::new(|parameters, action| match action {
+                                EditType::Right => {
+                                    let world = &mut parameters.world;
+                                    let mut uncapped = world.elevation_parameters;
+                                    uncapped.octaves += 1;
+
+                                    if uncapped.useful_octaves(world.width, world.height)
+                                        == uncapped.octaves
+                                    {
+                                        world.elevation_parameters.octaves += 1;
+                                    }
+                                }
+                                EditType::Left => {
+                                    let octaves =
+                                        &mut parameters.world.elevation_parameters.octaves;
+                                    *octaves = octaves.saturating_sub(1).max(1);
+                                }
+                                _ => {}
+                            }),
+                            reset: Box::new(|parameters, defaults| {
+                                parameters.world.elevation_parameters.octaves =
+                                    defaults.world.elevation_parameters.octaves
+                            }),
+                        },
+                        float_row!(
                             "persistence: {:.2}",
-                            parameters.world.elevation_parameters.persistence,
-                        )
-                    }),
-                    edit: Box::new(|parameters, action| match action {
-                        EditType::Right => {
-                            parameters.world.elevation_parameters.persistence += 0.05
-                        }
-                        EditType::Left => parameters.world.elevation_parameters.persistence -= 0.05,
-                        _ => {}
-                    }),
-                },
-                EditableRow {
-                    text: Box::new(|parameters| {
-                        format!(
+                            world.elevation_parameters.persistence,
+                            0.05
+                        ),
+                        float_row!(
                             "lacunarity: {:.2}",
-                            parameters.world.elevation_parameters.lacunarity,
-                        )
-                    }),
-                    edit: Box::new(|parameters, action| match action {
-                        EditType::Right => parameters.world.elevation_parameters.lacunarity += 0.05,
-                        EditType::Left => parameters.world.elevation_parameters.lacunarity -= 0.05,
-                        _ => {}
-                    }),
+                            world.elevation_parameters.lacunarity,
+                            0.05
+                        ),
+                        EditableRow {
+                            text: Box::new(|parameters| {
+                                format!(
+                                    "frequency: {:.2}",
+                                    parameters.world.elevation_parameters.frequency,
+                                )
+                            }),
+                            edit: Box::new(|parameters, action| match action {
+                                EditType::Right => {
+                                    parameters.world.elevation_parameters.frequency *= 1.1
+                                }
+                                EditType::Left => {
+                                    parameters.world.elevation_parameters.frequency /= 1.1
+                                }
+                                _ => {}
+                            }),
+                            reset: Box::new(|parameters, defaults| {
+                                parameters.world.elevation_parameters.frequency =
+                                    defaults.world.elevation_parameters.frequency
+                            }),
+                        },
+                        bool_row!(
+                            "rotate octaves: {}",
+                            world.elevation_parameters.rotate_octaves
+                        ),
+                        bool_row!(
+                            "preserve aspect: {}",
+                            world.elevation_parameters.preserve_aspect
+                        ),
+                        bool_row!("antialias: {}", world.elevation_parameters.antialias),
+                        bool_row!("wrap x: {}", world.elevation_parameters.wrap_x),
+                        bool_row!(
+                            "decorrelate octaves: {}",
+                            world.elevation_parameters.decorrelate_octaves
+                        ),
+                        EditableRow {
+                            text: Box::new(|parameters| {
+                                format!("reroll octave: {}", parameters.selected_octave)
+                            }),
+                            edit: Box::new(|parameters, action| match action {
+                                EditType::Right
+                                    if parameters.selected_octave + 1
+                                        < parameters.world.elevation_parameters.octaves =>
+                                {
+                                    parameters.selected_octave += 1
+                                }
+                                EditType::Left if parameters.selected_octave > 0 => {
+                                    parameters.selected_octave -= 1
+                                }
+                                _ => {}
+                            }),
+                            reset: Box::new(|parameters, defaults| {
+                                parameters.selected_octave = defaults.selected_octave
+                            }),
+                        },
+                    ],
                 },
-                EditableRow {
-                    text: Box::new(|parameters| {
-                        format!("scale: {:.2}", parameters.world.elevation_parameters.scale,)
-                    }),
-                    edit: Box::new(|parameters, action| match action {
-                        EditType::Right => parameters.world.elevation_parameters.scale *= 1.1,
-                        EditType::Left => parameters.world.elevation_parameters.scale /= 1.1,
-                        _ => {}
-                    }),
+                RowGroup {
+                    name: "Falloff",
+                    rows: vec![
+                        EditableRow {
+                            text: Box::new(|parameters| {
+                                format!(
+                                    "falloff: {}",
+                                    if parameters.world.falloff.is_some() {
+                                        '■'
+                                    } else {
+                                        '□'
+                                    },
+                                )
+                            }),
+                            edit: Box::new(|parameters, action| {
+                                if let EditType::Press = action {
+                                    parameters.world.falloff = match parameters.world.falloff {
+                                        Some(_) => None,
+                                        None => Some(FalloffParameters::default()),
+                                    }
+                                }
+                            }),
+                            reset: Box::new(|parameters, defaults| {
+                                parameters.world.falloff = defaults.world.falloff
+                            }),
+                        },
+                        option_row!(falloff, "falloff a", a, 0.1),
+                        option_row!(falloff, "falloff b", b, 0.1),
+                        option_row!(falloff, "falloff multiplier", multiplier, 0.05),
+                        option_bool_row!(falloff, "falloff top", top),
+                        option_bool_row!(falloff, "falloff bottom", bottom),
+                        option_bool_row!(falloff, "falloff left", left),
+                        option_bool_row!(falloff, "falloff right", right),
+                        EditableRow {
+                            text: Box::new(|parameters| {
+                                format!(
+                                    "polar suppression: {}",
+                                    if parameters.world.polar.is_some() {
+                                        '■'
+                                    } else {
+                                        '□'
+                                    },
+                                )
+                            }),
+                            edit: Box::new(|parameters, action| {
+                                if let EditType::Press = action {
+                                    parameters.world.polar = match parameters.world.polar {
+                                        Some(_) => None,
+                                        None => Some(PolarParameters::default()),
+                                    }
+                                }
+                            }),
+                            reset: Box::new(|parameters, defaults| {
+                                parameters.world.polar = defaults.world.polar
+                            }),
+                        },
+                        option_row!(polar, "polar band", band, 0.01),
+                        option_row!(polar, "polar transition", transition, 0.01),
+                        option_row!(polar, "polar multiplier", multiplier, 0.05),
+                    ],
+                },
+                RowGroup {
+                    name: "Erosion",
+                    rows: vec![
+                        float_row!("inertia: {:.2}", erosion.inertia, 0.05),
+                        float_row!("capacity: {:.1}", erosion.capacity, 0.5),
+                        float_row!("min capacity: {:.2}", erosion.min_capacity, 0.01),
+                        float_row!("deposition: {:.2}", erosion.deposition, 0.05),
+                        float_row!("erosion: {:.2}", erosion.erosion, 0.05),
+                        float_row!("evaporation: {:.2}", erosion.evaporation, 0.01),
+                        float_row!("gravity: {:.1}", erosion.gravity, 0.5),
+                        EditableRow {
+                            text: Box::new(|parameters| {
+                                format!("lifetime: {}", parameters.erosion.lifetime)
+                            }),
+                            edit: Box::new(|parameters, action| match action {
+                                EditType::Right => parameters.erosion.lifetime += 1,
+                                EditType::Left if parameters.erosion.lifetime > 1 => {
+                                    parameters.erosion.lifetime -= 1
+                                }
+                                _ => {}
+                            }),
+                            reset: Box::new(|parameters, defaults| {
+                                parameters.erosion.lifetime = defaults.erosion.lifetime
+                            }),
+                        },
+                    ],
+                },
+                RowGroup {
+                    name: "Display",
+                    rows: vec![
+                        float_row!("occlusion strength: {:.1}", occlusion_strength, 0.1),
+                        float_row!("vertical scale: {:.1}", vertical_scale, 0.1),
+                        float_row!("water depth scale: {:.1}", water_depth_scale, 0.5),
+                        float_row!("sun azimuth: {:.0}", sun_azimuth, 15.0),
+                        float_row!("sun altitude: {:.0}", sun_altitude, 5.0),
+                        EditableRow {
+                            text: Box::new(|parameters| {
+                                format!("supersample: {}", parameters.supersample)
+                            }),
+                            edit: Box::new(|parameters, action| match action {
+                                EditType::Right => parameters.supersample += 1,
+                                EditType::Left if parameters.supersample > 1 => {
+                                    parameters.supersample -= 1
+                                }
+                                _ => {}
+                            }),
+                            reset: Box::new(|parameters, defaults| {
+                                parameters.supersample = defaults.supersample
+                            }),
+                        },
+                        EditableRow {
+                            text: Box::new(|parameters| {
+                                format!("view mode: {:?}", parameters.view_mode)
+                            }),
+                            edit: Box::new(|parameters, action| match action {
+                                EditType::Right => {
+                                    parameters.view_mode = parameters.view_mode.next()
+                                }
+                                EditType::Left => {
+                                    parameters.view_mode = parameters.view_mode.previous()
+                                }
+                                _ => {}
+                            }),
+                            reset: Box::new(|parameters, defaults| {
+                                parameters.view_mode = defaults.view_mode
+                            }),
+                        },
+                        bool_row!("dither: {}", dither),
+                        EditableRow {
+                            text: Box::new(|parameters| {
+                                format!("color space: {:?}", parameters.color_space)
+                            }),
+                            edit: Box::new(|parameters, action| match action {
+                                EditType::Right => {
+                                    parameters.color_space = parameters.color_space.next()
+                                }
+                                EditType::Left => {
+                                    parameters.color_space = parameters.color_space.previous()
+                                }
+                                _ => {}
+                            }),
+                            reset: Box::new(|parameters, defaults| {
+                                parameters.color_space = defaults.color_space
+                            }),
+                        },
+                        bool_row!("show octaves: {}", show_octaves),
+                        bool_row!("show labels: {}", show_labels),
+                        bool_row!("show stats: {}", show_stats),
+                        bool_row!("snap values: {}", snap),
+                        bool_row!("shuffle seed: {}", shuffle_seed),
+                        bool_row!("lock seed: {}", lock_seed),
+                        bool_row!("auto generate: {}", auto_generate),
+                        float_row!("preview scale: {:.2}", preview_scale, 0.05),
+                    ],
                 },
-                bool_row!("shuffle seed: {}", shuffle_seed),
-                bool_row!("auto generate: {}", auto_generate),
             ],
         }
     }
 
+    pub fn set_gradient(&mut self, gradient: Gradient) {
+        self.gradient = Some(gradient);
+    }
+
     pub fn update_buffer(&mut self) {
-        self.buffer = (0..self.world.parameters.width * self.world.parameters.height)
-            .flat_map(|i| {
-                self.pixel_color(
-                    i % self.world.parameters.width,
-                    i / self.world.parameters.width,
-                )
-                .into_vec()
+        let supersample = self.parameters.supersample;
+        let cells = self.world.parameters.width * self.world.parameters.height;
+
+        // shading, labels and stats are filled in by render_step along with the rows
+        self.ambient_occlusion = if self.parameters.occlusion_strength > 0.0 {
+            vec![1.0; cells]
+        } else {
+            vec![]
+        };
+        self.hillshade = if self.parameters.view_mode == ViewMode::Relief {
+            vec![1.0; cells]
+        } else {
+            vec![]
+        };
+        self.occluded_rows = 0;
+        self.hillshaded_rows = 0;
+        self.labels_pending = true;
+        self.stats_pending = true;
+
+        if self.parameters.view_mode == ViewMode::Oblique {
+            let (buffer, width, height) = self.renderer().render();
+            self.buffer = buffer;
+            self.buffer_width = width;
+            self.buffer_height = height;
+            self.render_row = None;
+            self.render_step(Some(RENDER_BUDGET));
+            return;
+        }
+
+        // the rows are colored top-down over the next frames, a time budget at a time, over the
+        // previous image so that it stays on screen until it's replaced
+        let (width, height) = (
+            self.world.parameters.width * supersample,
+            self.world.parameters.height * supersample,
+        );
+        if (width, height) != (self.buffer_width, self.buffer_height) {
+            self.buffer = vec![0; width * height * 4];
+            self.buffer_width = width;
+            self.buffer_height = height;
+        }
+        self.render_row = Some(0);
+        self.render_step(Some(RENDER_BUDGET));
+    }
+
+    // does the pending work in order: the rows, then the labels, then the stats
+    fn render_step(&mut self, budget: Option<Duration>) {
+        let start = Instant::now();
+        let within_budget = || budget.is_none_or(|budget| start.elapsed() < budget);
+
+        while let Some(row) = self.render_row {
+            if !within_budget() {
+                return;
+            }
+            self.color_row(row);
+            self.render_row = if row + 1 < self.buffer_height {
+                Some(row + 1)
+            } else {
+                None
+            };
+        }
+
+        if self.labels_pending && within_budget() {
+            self.update_labels();
+            self.labels_pending = false;
+        }
+        if self.stats_pending && within_budget() {
+            self.stats = if self.parameters.show_stats {
+                Some(self.world.elevation.stats())
+            } else {
+                None
+            };
+            self.stats_pending = false;
+        }
+    }
+
+    fn color_row(&mut self, row: usize) {
+        self.shade_rows(row / self.parameters.supersample + 1);
+
+        let width = self.buffer_width;
+        let overlay = !self.selection.is_empty() || self.flood_preview;
+        let renderer = self.renderer();
+        let colors: Vec<u8> = (0..width)
+            .flat_map(|x| {
+                let color = renderer.pixel_color(x, row);
+                let color = if overlay {
+                    self.overlay_color(&renderer, x, row, color)
+                } else {
+                    color
+                };
+                color.into_vec()
             })
             .collect();
-    }
 
-    fn generate(&mut self, seed: u64) {
-        self.world = World::new(seed, self.parameters.world);
-        self.update_buffer();
+        self.buffer[row * width * 4..(row + 1) * width * 4].copy_from_slice(&colors);
     }
 
-    fn pixel_color(&self, x: usize, y: usize) -> Color {
-        let value = self.world.elevation.get(x, y);
+    // computes the shading of the cell rows above `end`, just ahead of the pixels that need it
+    fn shade_rows(&mut self, end: usize) {
+        let width = self.world.parameters.width;
+        let end = end.min(self.world.parameters.height);
 
-        if value < self.world.parameters.sea_level {
-            Self::interpolate_colors(
-                &self.colors.sea_low,
-                &self.colors.sea_high,
-                inverse_lerp(
-                    self.world.elevation.min,
-                    self.world
-                        .parameters
-                        .sea_level
-                        .min(self.world.elevation.max),
-                    value,
+        if self.ambient_occlusion.is_empty() {
+            self.occluded_rows = end;
+        }
+        while self.occluded_rows < end {
+            let y = self.occluded_rows;
+            self.ambient_occlusion[y * width..(y + 1) * width].copy_from_slice(
+                &self.world.elevation.ambient_occlusion_row(
+                    y,
+                    AMBIENT_OCCLUSION_RADIUS,
+                    self.parameters.vertical_scale,
                 ),
-            )
+            );
+            self.occluded_rows += 1;
+        }
+
+        if self.hillshade.is_empty() {
+            self.hillshaded_rows = end;
+        }
+        while self.hillshaded_rows < end {
+            let y = self.hillshaded_rows;
+            self.hillshade[y * width..(y + 1) * width].copy_from_slice(&coloring::hillshade_row(
+                &self.world,
+                y,
+                self.parameters.sun_azimuth,
+                self.parameters.sun_altitude,
+                self.parameters.vertical_scale,
+            ));
+            self.hillshaded_rows += 1;
+        }
+    }
+
+    // Only the hillshade depends on the sun: the rows not drawn yet are reshaded as the pass in
+    // progress reaches them, and a finished pass starts over without redoing anything else.
+    fn update_sun(&mut self) {
+        match self.render_row {
+            Some(row) => {
+                self.hillshaded_rows = self.hillshaded_rows.min(row / self.parameters.supersample)
+            }
+            None => {
+                self.hillshaded_rows = 0;
+                self.render_row = Some(0);
+            }
+        }
+    }
+
+    fn renderer(&self) -> Renderer<'_, f32> {
+        Renderer {
+            world: &self.world,
+            colors: &self.colors,
+            mode: self.parameters.view_mode,
+            gradient: self.gradient.as_ref(),
+            hypsometric_tints: &self.hypsometric_tints,
+            color_space: self.parameters.color_space,
+            water_depth_scale: self.parameters.water_depth_scale,
+            vertical_scale: self.parameters.vertical_scale,
+            dither: self.parameters.dither,
+            supersample: self.parameters.supersample,
+            ambient_occlusion: &self.ambient_occlusion,
+            occlusion_strength: self.parameters.occlusion_strength,
+            hillshade: &self.hillshade,
+        }
+    }
+
+    pub fn update_buffer_region(&mut self, x0: usize, y0: usize, x1: usize, y1: usize) {
+        let supersample = self.parameters.supersample;
+        if self.buffer_width != self.world.parameters.width * supersample
+            || self.buffer_height != self.world.parameters.height * supersample
+        {
+            self.update_buffer();
+            return;
+        }
+
+        // Hillshading reads the neighbouring cells and occlusion the horizon up to its radius,
+        // so an edit changes the shading, and the colors, of cells that far around it.
+        let (width, height) = (self.world.parameters.width, self.world.parameters.height);
+        if x0 >= x1.min(width) || y0 >= y1.min(height) {
+            return;
+        }
+        let padding = AMBIENT_OCCLUSION_RADIUS + 1;
+        let (x0, x1) = if self.world.elevation.wrap_x && (x0 < padding || x1 + padding > width) {
+            (0, width)
         } else {
-            Self::interpolate_colors(
-                &self.colors.land_low,
-                &self.colors.land_high,
+            (x0.saturating_sub(padding), (x1 + padding).min(width))
+        };
+        let (y0, y1) = (y0.saturating_sub(padding), (y1 + padding).min(height));
+
+        // rows shaded from here on already see the edit
+        let (occluded_rows, hillshaded_rows) = (self.occluded_rows, self.hillshaded_rows);
+        self.shade_rows(y1);
+        if !self.ambient_occlusion.is_empty() {
+            for y in y0..y1.min(occluded_rows) {
+                self.ambient_occlusion[y * width + x0..y * width + x1].copy_from_slice(
+                    &self.world.elevation.ambient_occlusion_span(
+                        y,
+                        x0,
+                        x1,
+                        AMBIENT_OCCLUSION_RADIUS,
+                        self.parameters.vertical_scale,
+                    ),
+                );
+            }
+        }
+        if !self.hillshade.is_empty() {
+            for y in y0..y1.min(hillshaded_rows) {
+                self.hillshade[y * width + x0..y * width + x1].copy_from_slice(
+                    &coloring::hillshade_span(
+                        &self.world,
+                        y,
+                        x0,
+                        x1,
+                        self.parameters.sun_azimuth,
+                        self.parameters.sun_altitude,
+                        self.parameters.vertical_scale,
+                    ),
+                );
+            }
+        }
+
+        let (x0, y0, x1, y1) = (
+            x0 * supersample,
+            y0 * supersample,
+            x1 * supersample,
+            y1 * supersample,
+        );
+
+        let renderer = self.renderer();
+        let mut colors = Vec::with_capacity((x1 - x0) * (y1 - y0));
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let color = renderer.pixel_color(x, y);
+                colors.push((x, y, self.overlay_color(&renderer, x, y, color)));
+            }
+        }
+
+        for (x, y, color) in colors {
+            let i = (y * self.buffer_width + x) * 4;
+            self.buffer[i..i + 4].copy_from_slice(&color.into_vec());
+        }
+    }
+
+    fn copy_parameters(&mut self) {
+        let parameters = format!(
+            "// seed: {}\n{}",
+            self.world.seed,
+            self.parameters.world.to_rust()
+        );
+        let result =
+            ClipboardContext::new().and_then(|mut clipboard| clipboard.set_contents(parameters));
+
+        self.set_status(match result {
+            Ok(()) => "copied parameters to clipboard".to_string(),
+            Err(e) => format!("could not copy parameters: {}", e),
+        });
+    }
+
+    fn update_labels(&mut self) {
+        self.labels.clear();
+        if !self.parameters.show_labels {
+            return;
+        }
+
+        let width = self.world.parameters.width;
+        let land_mask = self.world.land_mask();
+
+        let mut landmasses: HashMap<usize, (usize, usize, usize)> = HashMap::new();
+        for (i, label) in self
+            .world
+            .regions(Connectivity::default())
+            .into_iter()
+            .enumerate()
+        {
+            if land_mask[i] {
+                let landmass = landmasses.entry(label).or_insert((0, 0, 0));
+                landmass.0 += i % width;
+                landmass.1 += i / width;
+                landmass.2 += 1;
+            }
+        }
+
+        let mut landmasses: Vec<_> = landmasses
+            .into_values()
+            .filter(|&(_, _, size)| size >= LABEL_MIN_LANDMASS)
+            .collect();
+        landmasses.sort_by(|a, b| b.2.cmp(&a.2).then(a.cmp(b)));
+
+        for (sum_x, sum_y, size) in landmasses {
+            let (x, y) = (sum_x / size, sum_y / size);
+            self.labels.push(Label {
+                text: self.world.name_feature(FeatureKind::Continent, x, y),
+                x,
+                y,
+            });
+        }
+
+        let range = self.world.elevation.max - self.world.elevation.min;
+        let sea_level = self.world.sea_level;
+        let peaks = self
+            .world
+            .elevation
+            .peaks(range * LABEL_PEAK_PROMINENCE)
+            .into_iter()
+            .filter(|&(x, y, _)| self.world.elevation.get(x, y) >= sea_level)
+            .take(LABEL_MAX_PEAKS)
+            .collect::<Vec<_>>();
+
+        for (x, y, _) in peaks {
+            self.labels.push(Label {
+                text: self.world.name_feature(FeatureKind::Peak, x, y),
+                x,
+                y,
+            });
+        }
+    }
+
+    fn draw_labels(&self, ctx: &mut Context) -> GameResult<()> {
+        let mut placed: Vec<graphics::Rect> = vec![];
+
+        for label in &self.labels {
+            let text = graphics::Text::new(
+                TextFragment::new(label.text.as_str())
+                    .font(*self.font)
+                    .scale(graphics::Scale::uniform(self.hud_scale)),
+            );
+            let (width, height) = text.dimensions(ctx);
+            let [x, y] = self.map_to_screen(label.x as f32 + 0.5, label.y as f32 + 0.5);
+            let rect = graphics::Rect::new(
+                x - width as f32 / 2.0,
+                y - height as f32 / 2.0,
+                width as f32,
+                height as f32,
+            );
+
+            if placed.iter().any(|other| other.overlaps(&rect)) {
+                continue;
+            }
+
+            graphics::draw(
+                ctx,
+                &text,
+                DrawParam {
+                    dest: [rect.x + 1.0, rect.y + 1.0].into(),
+                    color: graphics::BLACK,
+                    ..Default::default()
+                },
+            )?;
+            graphics::draw(
+                ctx,
+                &text,
+                DrawParam {
+                    dest: [rect.x, rect.y].into(),
+                    ..Default::default()
+                },
+            )?;
+            placed.push(rect);
+        }
+
+        Ok(())
+    }
+
+    fn draw_help(&self, ctx: &mut Context) -> GameResult<()> {
+        let text = graphics::Text::new(
+            TextFragment::new(self.key_bindings.help())
+                .font(*self.font)
+                .scale(graphics::Scale::uniform(self.hud_scale)),
+        );
+        let (width, height) = text.dimensions(ctx);
+        let origin = [
+            (self.window_size[0] - width as f32) / 2.0,
+            (self.window_size[1] - height as f32) / 2.0,
+        ];
+
+        let background = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(
+                origin[0] - HUD_PADDING,
+                origin[1] - HUD_PADDING,
+                width as f32 + HUD_PADDING * 2.0,
+                height as f32 + HUD_PADDING * 2.0,
+            ),
+            HUD_BACKGROUND,
+        )?;
+        graphics::draw(ctx, &background, DrawParam::default())?;
+        graphics::draw(
+            ctx,
+            &text,
+            DrawParam {
+                dest: origin.into(),
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn export_legend<P: AsRef<Path>>(&self, path: P) -> Result<(), RealmsError> {
+        let height = LEGEND_BAR_HEIGHT + LEGEND_MARGIN * 2;
+        let mut buffer = [32, 32, 32, 255].repeat(LEGEND_WIDTH * height);
+
+        let (min, max) = (self.world.elevation.min, self.world.elevation.max);
+        let row_of = |value: f64| {
+            LEGEND_MARGIN
+                + ((1.0 - inverse_lerp(min, max, value)) * (LEGEND_BAR_HEIGHT - 1) as f64).round()
+                    as usize
+        };
+
+        for row in 0..LEGEND_BAR_HEIGHT {
+            let value = lerp(max, min, row as f64 / (LEGEND_BAR_HEIGHT - 1) as f64);
+            let color = self.legend_color(value).into_vec();
+            for x in LEGEND_MARGIN..LEGEND_MARGIN + LEGEND_BAR_WIDTH {
+                let index = ((LEGEND_MARGIN + row) * LEGEND_WIDTH + x) * 4;
+                buffer[index..index + 4].copy_from_slice(&color);
+            }
+        }
+
+        let sea_level = self.world.sea_level;
+        let mut ticks: Vec<f64> = (0..LEGEND_TICKS)
+            .map(|i| lerp(min, max, i as f64 / (LEGEND_TICKS - 1) as f64))
+            .collect();
+        if sea_level > min && sea_level < max {
+            ticks.push(sea_level);
+        }
+
+        let text_height = legend::text_height(LEGEND_TEXT_SCALE);
+        for value in ticks {
+            let row = row_of(value);
+            let tick_x = LEGEND_MARGIN + LEGEND_BAR_WIDTH;
+            for x in tick_x..tick_x + 4 {
+                let index = (row * LEGEND_WIDTH + x) * 4;
+                buffer[index..index + 4].copy_from_slice(&[255, 255, 255, 255]);
+            }
+
+            let top = row
+                .saturating_sub(text_height / 2)
+                .min(height - text_height);
+            legend::draw_text(
+                &mut buffer,
+                LEGEND_WIDTH,
+                (tick_x + 8, top),
+                &format!("{:.2}", value),
+                LEGEND_TEXT_SCALE,
+                [255, 255, 255, 255],
+            );
+        }
+
+        image::save_buffer(
+            path,
+            &buffer,
+            LEGEND_WIDTH as u32,
+            height as u32,
+            image::ColorType::Rgba8,
+        )?;
+        Ok(())
+    }
+
+    fn legend_color(&self, value: f64) -> Color {
+        let sea_level = self.world.sea_level;
+        match (self.parameters.view_mode, &self.gradient) {
+            (ViewMode::Gradient, Some(gradient)) => gradient.sample(
+                self.world.elevation.normalize(value),
+                0.0,
+                self.parameters.color_space,
+            ),
+            (ViewMode::Relief, _) if value >= sea_level => self.hypsometric_tints.sample(
                 inverse_lerp(
-                    self.world
-                        .parameters
-                        .sea_level
-                        .max(self.world.elevation.min),
+                    sea_level.max(self.world.elevation.min),
                     self.world.elevation.max,
                     value,
                 ),
-            )
+                0.0,
+                self.parameters.color_space,
+            ),
+            (ViewMode::Grayscale, _) => {
+                let gray = (self.world.elevation.normalize(value) * 255.0) as u8;
+                Color::rgb(gray, gray, gray)
+            }
+            _ => self.renderer().elevation_color(value, 0.0),
         }
     }
 
-    fn interpolate_colors(a: &Color, b: &Color, value: f64) -> Color {
-        Color::rgb(
-            Self::interpolate_u8(a.r, b.r, value),
-            Self::interpolate_u8(a.g, b.g, value),
-            Self::interpolate_u8(a.b, b.b, value),
-        )
+    fn save_screenshot(&mut self, ctx: &mut Context) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        let path = format!("realms-{}-{}.png", self.world.seed, timestamp);
+
+        let result = (|| -> Result<(), Box<dyn Error>> {
+            let screenshot = graphics::screenshot(ctx)?;
+            let pixels = screenshot.to_rgba8(ctx)?;
+            image::save_buffer(
+                &path,
+                &pixels,
+                screenshot.width() as u32,
+                screenshot.height() as u32,
+                image::ColorType::Rgba8,
+            )?;
+            Ok(())
+        })();
+
+        self.set_status(match result {
+            Ok(()) => format!("saved screenshot to {}", path),
+            Err(e) => format!("could not save screenshot: {}", e),
+        });
+    }
+
+    // Returns true when the action consumes the key, so no other action bound to it runs.
+    fn perform(&mut self, ctx: &mut Context, action: Action) -> bool {
+        match action {
+            Action::Regenerate if self.parameters.lock_seed => {
+                self.set_status(format!("seed {} is locked", self.world.seed));
+            }
+            Action::Regenerate => {
+                self.generate(if self.parameters.shuffle_seed {
+                    thread_rng().next_u64()
+                } else {
+                    self.world.seed
+                });
+            }
+            Action::FloodPreview => {
+                self.flood_preview = true;
+                self.set_status(format!("flood: sea level +{:.2}", self.flood_delta));
+                self.update_buffer();
+            }
+            Action::ToggleErosion => {
+                self.erosion_stepping = !self.erosion_stepping;
+                self.erosion_world = None;
+                self.set_status(format!(
+                    "erosion stepping: {}",
+                    if self.erosion_stepping { "on" } else { "off" }
+                ));
+            }
+            Action::ErosionStep if self.erosion_stepping => {
+                let (x0, y0, x1, y1) = self.erode_step();
+                self.update_buffer_region(x0, y0, x1, y1);
+                self.set_status(format!("erosion: {} droplets", self.world.droplets));
+                return true;
+            }
+            Action::ErosionStep => {}
+            Action::Variant => {
+                self.world = self.world.variant(VARIANT_AMOUNT);
+                self.erosion_world = None;
+                self.update_buffer();
+                self.update_octave_buffers();
+            }
+            Action::Wavelength => {
+                let wavelength = self.world.elevation.dominant_wavelength();
+                self.set_status(format!("dominant wavelength: {:.0} cells", wavelength));
+            }
+            Action::CopyParameters => self.copy_parameters(),
+            Action::Randomize => {
+                self.parameters.world.randomize(&mut thread_rng());
+                self.parameters.selected_octave = 0;
+                self.generate(if self.parameters.lock_seed {
+                    self.world.seed
+                } else {
+                    thread_rng().next_u64()
+                });
+                self.set_status("randomized parameters".to_string());
+            }
+            Action::Sweep => {
+                if self.sweep.is_none() {
+                    self.start_sweep();
+                }
+            }
+            Action::ExportBiomes => {
+                let path = format!("realms-{}-biomes.png", self.world.seed);
+                self.set_status(match self.world.export_biome_png(&path) {
+                    Ok(()) => format!("saved biome map to {}", path),
+                    Err(e) => format!("could not save biome map: {}", e),
+                });
+            }
+            Action::ExportAsc => {
+                let path = format!("realms-{}.asc", self.world.seed);
+                self.set_status(match self.world.export_asc(&path) {
+                    Ok(()) => format!("saved elevation grid to {}", path),
+                    Err(e) => format!("could not save elevation grid: {}", e),
+                });
+            }
+            #[cfg(feature = "json")]
+            Action::ExportJson => {
+                let path = format!("realms-{}.json", self.world.seed);
+                self.set_status(match self.world.to_metadata_json(&path) {
+                    Ok(()) => format!("saved metadata to {}", path),
+                    Err(e) => format!("could not save metadata: {}", e),
+                });
+            }
+            #[cfg(not(feature = "json"))]
+            Action::ExportJson => {
+                self.set_status("metadata export needs the json feature".to_string());
+            }
+            Action::ExportSvg => {
+                let path = format!("realms-{}.svg", self.world.seed);
+                self.set_status(
+                    match self
+                        .world
+                        .export_svg(&path, &SvgOptions::default(), &self.colors)
+                    {
+                        Ok(()) => format!("saved vector map to {}", path),
+                        Err(e) => format!("could not save vector map: {}", e),
+                    },
+                );
+            }
+            Action::ExportResampled => {
+                let path = format!("realms-{}-x{}.png", self.world.seed, EXPORT_RESAMPLE_FACTOR);
+                let width = self.world.parameters.width as u32 * EXPORT_RESAMPLE_FACTOR;
+                let height = self.world.parameters.height as u32 * EXPORT_RESAMPLE_FACTOR;
+                let result = self
+                    .world
+                    .export_png_resampled(&path, width, height, &self.colors);
+
+                self.set_status(match result {
+                    Ok(()) => format!("saved {}x{} map to {}", width, height, path),
+                    Err(e) => format!("could not save map: {}", e),
+                });
+            }
+            Action::ExportLegend => {
+                let path = format!("realms-{}-legend.png", self.world.seed);
+                self.set_status(match self.export_legend(&path) {
+                    Ok(()) => format!("saved legend to {}", path),
+                    Err(e) => format!("could not save legend: {}", e),
+                });
+            }
+            Action::ToggleLabels => {
+                self.parameters.show_labels = !self.parameters.show_labels;
+                self.update_labels();
+            }
+            Action::SaveFavorite => self.save_favorite(),
+            Action::PreviousFavorite => self.recall_favorite(-1),
+            Action::NextFavorite => self.recall_favorite(1),
+            Action::Screenshot => self.save_screenshot(ctx),
+            Action::RerollOctave => self.reroll_selected_octave(),
+            Action::RerollDetail => {
+                self.world.reroll_detail(self.parameters.selected_octave);
+                self.erosion_world = None;
+                self.update_buffer();
+                self.update_octave_buffers();
+                self.set_status(format!(
+                    "rerolled detail from octave {}",
+                    self.parameters.selected_octave
+                ));
+            }
+            Action::JumpHighest => self.center_on_index(self.world.elevation.max_index),
+            Action::JumpLowest => self.center_on_index(self.world.elevation.min_index),
+            Action::FitToWindow => self.fit_to_window(),
+            Action::PreviousGroup => {
+                let groups = self.groups.len();
+                self.select_group((self.current_group + groups - 1) % groups);
+            }
+            Action::NextGroup => self.select_group((self.current_group + 1) % self.groups.len()),
+            Action::Group1 => self.select_group(0),
+            Action::Group2 => self.select_group(1),
+            Action::Group3 => self.select_group(2),
+            Action::Group4 => self.select_group(3),
+            Action::PreviousRow => {
+                let rows = self.groups[self.current_group].rows.len();
+                self.current_row = (self.current_row + rows - 1) % rows;
+            }
+            Action::NextRow => {
+                let rows = self.groups[self.current_group].rows.len();
+                self.current_row = (self.current_row + 1) % rows;
+            }
+            Action::ResetRow => self.reset_current_row(),
+            Action::Increase => self.edit_current_row(EditType::Right),
+            Action::Decrease => self.edit_current_row(EditType::Left),
+            Action::Press => self.edit_current_row(EditType::Press),
+            Action::NextTool => {
+                self.tool = self.tool.next();
+                self.measure_points.clear();
+                self.path = None;
+                self.clear_selection();
+                self.set_status(format!("tool: {:?}", self.tool));
+            }
+            Action::SunSweep => {
+                self.sun_sweeping = !self.sun_sweeping;
+                self.set_status(format!(
+                    "moving sun: {}",
+                    if self.sun_sweeping { "on" } else { "off" }
+                ));
+            }
+            Action::Help => self.show_help = !self.show_help,
+        }
+        false
+    }
+
+    fn select_group(&mut self, group: usize) {
+        if group < self.groups.len() {
+            self.current_group = group;
+            self.current_row = 0;
+        }
+    }
+
+    fn select_region(&mut self, x: usize, y: usize) {
+        let regions = self.world.regions(Connectivity::default());
+        let region = regions[y * self.world.parameters.width + x];
+
+        self.selection = regions.into_iter().map(|label| label == region).collect();
+        self.selection_size = self.selection.iter().filter(|&&selected| selected).count();
+        self.update_buffer();
     }
 
-    fn interpolate_u8(a: u8, b: u8, value: f64) -> u8 {
-        lerp(a as f64, b as f64, value) as u8
+    fn clear_selection(&mut self) {
+        if !self.selection.is_empty() {
+            self.selection.clear();
+            self.selection_size = 0;
+            self.update_buffer();
+        }
+    }
+
+    fn edit_current_row(&mut self, action: EditType) {
+        let previous = self.parameters.clone();
+        let row = &mut self.groups[self.current_group].rows[self.current_row];
+        (row.edit)(&mut self.parameters, action);
+        // the edit may have been clamped to no change at all
+        if self.parameters != previous {
+            // edits in quick succession (key repeat, scrolling) only get a preview until they stop
+            let rapid = self
+                .last_edit
+                .is_some_and(|last_edit| last_edit.elapsed() < PREVIEW_DEBOUNCE);
+            self.last_edit = Some(Instant::now());
+
+            if rapid && self.parameters.preview_scale < 1.0 {
+                self.generate_preview();
+            } else {
+                self.apply_parameters(self.parameters.auto_generate);
+            }
+        }
+    }
+
+    fn reset_current_row(&mut self) {
+        let previous = self.parameters.clone();
+        let row = &self.groups[self.current_group].rows[self.current_row];
+        (row.reset)(&mut self.parameters, &self.defaults);
+        if self.parameters != previous {
+            self.apply_parameters(true);
+        }
+    }
+
+    fn apply_parameters(&mut self, regenerate: bool) {
+        if self
+            .world
+            .parameters
+            .needs_regeneration(&self.parameters.world)
+        {
+            if !regenerate {
+                return;
+            }
+            if !self.octaves_only_changed() {
+                self.generate(self.world.seed);
+                return;
+            }
+
+            self.world
+                .set_octaves(self.parameters.world.elevation_parameters.octaves);
+            self.erosion_world = None;
+            self.preview_pending = false;
+            self.selection.clear();
+            self.selection_size = 0;
+            self.octave_buffers.clear();
+        }
+
+        // the rest of the elevation is still valid, so only the coloring needs updating
+        self.world.set_sea_level(self.parameters.world.sea_level);
+        self.update_buffer();
+        if self.parameters.show_octaves == self.octave_buffers.is_empty() {
+            self.update_octave_buffers();
+        }
+    }
+
+    // also true when nothing but the coloring changed
+    fn octaves_only_changed(&self) -> bool {
+        let mut parameters = self.world.parameters;
+        parameters.elevation_parameters.octaves =
+            self.parameters.world.elevation_parameters.octaves;
+        !parameters.needs_regeneration(&self.parameters.world)
+    }
+
+    fn save_favorite(&mut self) {
+        self.favorites.push(Favorite {
+            seed: self.world.seed,
+            parameters: self.parameters.world,
+        });
+        self.favorite_index = self.favorites.len() - 1;
+
+        self.set_status(match favorites::save(FAVORITES_PATH, &self.favorites) {
+            Ok(()) => format!("saved favorite {}", self.favorites.len()),
+            Err(e) => format!("could not save favorites: {}", e),
+        });
+    }
+
+    fn recall_favorite(&mut self, step: isize) {
+        if self.favorites.is_empty() {
+            self.set_status("no favorites saved".to_string());
+            return;
+        }
+
+        let count = self.favorites.len() as isize;
+        self.favorite_index = (self.favorite_index as isize + step).rem_euclid(count) as usize;
+
+        let favorite = self.favorites[self.favorite_index];
+        self.parameters.world = favorite.parameters;
+        self.parameters.selected_octave = 0;
+        self.generate(favorite.seed);
+        self.set_status(format!(
+            "favorite {}/{}: seed {}",
+            self.favorite_index + 1,
+            count,
+            favorite.seed
+        ));
+    }
+
+    fn start_sweep(&mut self) {
+        let initial = self.parameters.clone();
+        for _ in 0..SWEEP_FRAMES / 2 {
+            (self.groups[self.current_group].rows[self.current_row].edit)(
+                &mut self.parameters,
+                EditType::Left,
+            );
+        }
+        self.apply_parameters(true);
+
+        self.sweep = Some(Sweep {
+            group: self.current_group,
+            row: self.current_row,
+            frame: 0,
+            initial,
+        });
+    }
+
+    fn sweep_step(&mut self) {
+        // frames are saved from the buffer, so it has to be complete
+        self.render_step(None);
+
+        let sweep = match &mut self.sweep {
+            Some(sweep) => sweep,
+            None => return,
+        };
+
+        let path = format!("realms-{}-sweep-{:03}.png", self.world.seed, sweep.frame);
+        let result = image::save_buffer(
+            &path,
+            &self.buffer,
+            self.buffer_width as u32,
+            self.buffer_height as u32,
+            image::ColorType::Rgba8,
+        );
+        sweep.frame += 1;
+
+        if let Err(e) = result {
+            self.parameters = self.sweep.take().unwrap().initial;
+            self.apply_parameters(true);
+            self.set_status(format!("could not save sweep frame: {}", e));
+        } else if sweep.frame > SWEEP_FRAMES {
+            self.parameters = self.sweep.take().unwrap().initial;
+            self.apply_parameters(true);
+            self.set_status(format!("saved {} sweep frames", SWEEP_FRAMES + 1));
+        } else {
+            let (group, row, frame) = (sweep.group, sweep.row, sweep.frame);
+            self.set_status(format!("sweep: frame {}/{}", frame, SWEEP_FRAMES));
+            (self.groups[group].rows[row].edit)(&mut self.parameters, EditType::Right);
+            self.apply_parameters(true);
+        }
+    }
+
+    fn set_status(&mut self, status: String) {
+        self.status = Some((status, Instant::now()));
+    }
+
+    pub fn resize(&mut self, width: f32, height: f32) {
+        self.window_size = [width, height];
+        // whole multiples of the font size keep the pixel font crisp
+        self.hud_scale = HUD_FONT_SIZE * (height / HUD_REFERENCE_HEIGHT).round().max(1.0);
+        if !self.user_zoomed {
+            self.fit_to_window();
+        }
+    }
+
+    pub fn fit_to_window(&mut self) {
+        self.user_zoomed = false;
+        self.scale = f32::min(
+            self.window_size[0] / self.world.parameters.width as f32,
+            self.window_size[1] / self.world.parameters.height as f32,
+        );
+        self.offset = [0.0, 0.0];
+    }
+
+    fn center_on(&mut self, x: usize, y: usize) {
+        let fit_scale = f32::min(
+            self.window_size[0] / self.world.parameters.width as f32,
+            self.window_size[1] / self.world.parameters.height as f32,
+        );
+        self.scale = self.scale.max(fit_scale * JUMP_ZOOM);
+        self.user_zoomed = true;
+        self.offset = [
+            (x as f32 + 0.5) / self.world.parameters.width as f32 - 0.5,
+            (y as f32 + 0.5) / self.world.parameters.height as f32 - 0.5,
+        ];
+    }
+
+    fn center_on_index(&mut self, i: usize) {
+        let width = self.world.parameters.width;
+        self.center_on(i % width, i / width);
+    }
+
+    fn map_to_screen(&self, x: f32, y: f32) -> [f32; 2] {
+        let origin = self.map_origin();
+        [
+            origin[0] + (x - self.offset[0] * self.world.parameters.width as f32) * self.scale,
+            origin[1] + (y - self.offset[1] * self.world.parameters.height as f32) * self.scale,
+        ]
+    }
+
+    fn screen_to_cell(&self, x: f32, y: f32) -> Option<(usize, usize)> {
+        let origin = self.map_origin();
+        let cell_x =
+            (x - origin[0]) / self.scale + self.offset[0] * self.world.parameters.width as f32;
+        let cell_y =
+            (y - origin[1]) / self.scale + self.offset[1] * self.world.parameters.height as f32;
+
+        if cell_x < 0.0
+            || cell_y < 0.0
+            || cell_x >= self.world.parameters.width as f32
+            || cell_y >= self.world.parameters.height as f32
+        {
+            return None;
+        }
+        Some((cell_x as usize, cell_y as usize))
+    }
+
+    fn map_origin(&self) -> [f32; 2] {
+        [
+            (self.window_size[0] - self.world.parameters.width as f32 * self.scale) / 2.0,
+            (self.window_size[1] - self.world.parameters.height as f32 * self.scale) / 2.0,
+        ]
+    }
+
+    fn generate(&mut self, seed: u64) {
+        self.world = self.cached_world(seed);
+        self.erosion_world = None;
+        self.preview_pending = false;
+        self.selection.clear();
+        self.selection_size = 0;
+        self.update_buffer();
+        self.update_octave_buffers();
+    }
+
+    // revisiting a seed and parameters (favorites, sweeps, rerolls) reuses the generated world,
+    // least recently used first out
+    fn cached_world(&mut self, seed: u64) -> World<f32> {
+        let parameters = self.parameters.world;
+        let position = self.world_cache.iter().position(|world| {
+            world.seed == seed && !world.parameters.needs_regeneration(&parameters)
+        });
+
+        let mut world = match position.and_then(|i| self.world_cache.remove(i)) {
+            Some(world) => world,
+            None => World::new(seed, parameters),
+        };
+        world.set_sea_level(parameters.sea_level);
+
+        if self.world_cache.len() >= WORLD_CACHE_SIZE {
+            self.world_cache.pop_front();
+        }
+        self.world_cache.push_back(world.clone());
+        world
+    }
+
+    fn generate_preview(&mut self) {
+        if !self.parameters.auto_generate || self.octaves_only_changed() {
+            self.apply_parameters(self.parameters.auto_generate);
+            return;
+        }
+
+        self.world = World::preview(
+            self.world.seed,
+            self.parameters.world,
+            self.parameters.preview_scale,
+        );
+        self.erosion_world = None;
+        self.preview_pending = true;
+        self.selection.clear();
+        self.selection_size = 0;
+        self.update_buffer();
+    }
+
+    // Droplets move heights by amounts f32 rounds away, so they run on an f64 copy of the world
+    // and only the cells they touched are copied back for display. Anything that replaces or
+    // edits the displayed world drops the copy so that it gets remade from it.
+    fn erode_step(&mut self) -> (usize, usize, usize, usize) {
+        let world = &self.world;
+        let eroding = self.erosion_world.get_or_insert_with(|| world.converted());
+        let (x0, y0, x1, y1) = eroding.erode_step(&self.parameters.erosion);
+        for y in y0..y1.min(self.world.parameters.height) {
+            for x in x0..x1.min(self.world.parameters.width) {
+                self.world.elevation.set(x, y, eroding.elevation.get(x, y));
+            }
+        }
+        self.world.elevation.update_bounds_in(x0, y0, x1, y1);
+        self.world.elevation_edited();
+        self.world.droplets = eroding.droplets;
+
+        (x0, y0, x1, y1)
+    }
+
+    fn reroll_selected_octave(&mut self) {
+        let octave = self.parameters.selected_octave;
+        if octave < self.world.octave_offsets.len() {
+            self.world.reroll_octave(octave, thread_rng().next_u64());
+            self.erosion_world = None;
+            self.update_buffer();
+            self.update_octave_buffers();
+        }
+    }
+
+    fn update_octave_buffers(&mut self) {
+        if !self.parameters.show_octaves {
+            self.octave_buffers.clear();
+            return;
+        }
+
+        let (_, layers) = NoiseMap::<f32>::new_with_layers(
+            self.world.seed,
+            self.world.parameters.width,
+            self.world.parameters.height,
+            &self.world.parameters.elevation_parameters,
+            &self.world.octave_offsets,
+        );
+
+        // each octave gets its own hue so the thumbnails can be told apart at a glance
+        let hue_step = 360.0 / layers.len() as f64;
+        self.octave_buffers = layers
+            .iter()
+            .enumerate()
+            .map(|(octave, layer)| {
+                let min = layer.iter().cloned().fold(f64::INFINITY, f64::min);
+                let max = layer.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+
+                layer
+                    .iter()
+                    .flat_map(|&value| {
+                        let value = inverse_lerp(min, max, value);
+                        Color::from_hsv(octave as f64 * hue_step, OCTAVE_SATURATION, value)
+                            .into_vec()
+                    })
+                    .collect()
+            })
+            .collect();
+    }
+
+    fn overlay_color(&self, renderer: &Renderer<f32>, x: usize, y: usize, color: Color) -> Color {
+        let supersample = self.parameters.supersample;
+        let color = if !self.selection.is_empty()
+            && self.selection[(y / supersample) * self.world.parameters.width + x / supersample]
+        {
+            SELECTION_COLOR.over(&color)
+        } else {
+            color
+        };
+
+        if !self.flood_preview {
+            return color;
+        }
+
+        let value = renderer.pixel_elevation(x, y);
+        let sea_level = self.world.sea_level;
+        if value >= sea_level && value < sea_level + self.flood_delta {
+            FLOOD_COLOR.over(&color)
+        } else {
+            color
+        }
     }
 }
 
 impl<'f> EventHandler for WorldViewer<'f> {
-    fn update(&mut self, _ctx: &mut Context) -> GameResult<()> {
+    fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
+        self.render_step(Some(RENDER_BUDGET));
+
+        if self.sweep.is_some() {
+            self.sweep_step();
+        }
+
+        if self.preview_pending
+            && self
+                .last_edit
+                .is_none_or(|last_edit| last_edit.elapsed() >= PREVIEW_DEBOUNCE)
+        {
+            self.generate(self.world.seed);
+        }
+
+        if self.sun_sweeping {
+            let elapsed = timer::duration_to_f64(timer::delta(ctx));
+            self.parameters.sun_azimuth =
+                (self.parameters.sun_azimuth + SUN_SWEEP_SPEED * elapsed).rem_euclid(360.0);
+            if self.parameters.view_mode == ViewMode::Relief {
+                self.update_sun();
+            }
+        }
         Ok(())
     }
 
@@ -230,23 +1757,100 @@ impl<'f> EventHandler for WorldViewer<'f> {
 
         let mut image = graphics::Image::from_rgba8(
             ctx,
-            self.world.parameters.width as u16,
-            self.world.parameters.height as u16,
+            self.buffer_width as u16,
+            self.buffer_height as u16,
             &self.buffer,
         )?;
         image.set_filter(graphics::FilterMode::Nearest);
 
+        let pixel_scale =
+            self.scale * self.world.parameters.width as f32 / self.buffer_width as f32;
+
         graphics::draw(
             ctx,
             &image,
             DrawParam {
-                scale: [self.scale, self.scale].into(),
+                dest: self.map_origin().into(),
+                scale: [pixel_scale, pixel_scale].into(),
                 offset: self.offset.into(),
                 ..Default::default()
             },
         )?;
 
-        let text = self
+        let thumbnail_scale = OCTAVE_THUMBNAIL_SIZE / self.world.parameters.width as f32;
+        let thumbnail_height = self.world.parameters.height as f32 * thumbnail_scale;
+        for (i, octave_buffer) in self.octave_buffers.iter().enumerate() {
+            let image = graphics::Image::from_rgba8(
+                ctx,
+                self.world.parameters.width as u16,
+                self.world.parameters.height as u16,
+                octave_buffer,
+            )?;
+
+            graphics::draw(
+                ctx,
+                &image,
+                DrawParam {
+                    dest: [
+                        4.0 + i as f32 * (OCTAVE_THUMBNAIL_SIZE + 4.0),
+                        self.window_size[1] - thumbnail_height - 4.0,
+                    ]
+                    .into(),
+                    scale: [thumbnail_scale, thumbnail_scale].into(),
+                    ..Default::default()
+                },
+            )?;
+        }
+
+        let cursor = mouse::position(ctx);
+        let hovered = self.screen_to_cell(cursor.x, cursor.y);
+        if let Some((x, y)) = hovered {
+            let [screen_x, screen_y] = self.map_to_screen(x as f32, y as f32);
+            let outline = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::stroke(1.0),
+                graphics::Rect::new(screen_x, screen_y, self.scale, self.scale),
+                graphics::WHITE,
+            )?;
+            graphics::draw(ctx, &outline, DrawParam::default())?;
+        }
+
+        if let Some(path) = &self.path {
+            let points: Vec<[f32; 2]> = path
+                .iter()
+                .map(|&(x, y)| self.map_to_screen(x as f32 + 0.5, y as f32 + 0.5))
+                .collect();
+            if points.len() >= 2 {
+                let line = graphics::Mesh::new_line(ctx, &points, 2.0, graphics::WHITE)?;
+                graphics::draw(ctx, &line, DrawParam::default())?;
+            }
+        } else if let (Tool::Measure, [start, end]) = (self.tool, &self.measure_points[..]) {
+            let points = [
+                self.map_to_screen(start.0 as f32 + 0.5, start.1 as f32 + 0.5),
+                self.map_to_screen(end.0 as f32 + 0.5, end.1 as f32 + 0.5),
+            ];
+            let line = graphics::Mesh::new_line(ctx, &points, 2.0, graphics::WHITE)?;
+            graphics::draw(ctx, &line, DrawParam::default())?;
+        }
+
+        self.draw_labels(ctx)?;
+
+        let group = &self.groups[self.current_group];
+        let tabs = self
+            .groups
+            .iter()
+            .enumerate()
+            .map(|(i, group)| {
+                if i == self.current_group {
+                    format!("[{}]", group.name)
+                } else {
+                    format!(" {} ", group.name)
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("");
+
+        let rows = group
             .rows
             .iter()
             .enumerate()
@@ -260,26 +1864,127 @@ impl<'f> EventHandler for WorldViewer<'f> {
             })
             .collect::<Vec<String>>()
             .join("\n");
+        let hud_scale = graphics::Scale::uniform(self.hud_scale);
+        let mut hud = format!("{}\n\n{}", tabs, rows);
+        if self.tool != Tool::Pan {
+            hud.push_str(&format!("\n\ntool: {:?}", self.tool));
+        }
+        if let Some((x, y)) = hovered {
+            let cell = self.world.cell(x, y);
+            hud.push_str(&format!(
+                "\n\n{}, {}: {:.3} ({:.0}%)\n{:?}",
+                x,
+                y,
+                cell.elevation,
+                cell.normalized_elevation * 100.0,
+                cell.biome
+            ));
+        }
+        if let Some(stats) = &self.stats {
+            hud.push_str(&format!(
+                "\n\nmin {:.2}  max {:.2}\nmean {:.2}  stddev {:.2}\nmedian {:.2}",
+                stats.min, stats.max, stats.mean, stats.stddev, stats.median
+            ));
+        }
+        if !self.selection.is_empty() {
+            hud.push_str(&format!("\nregion: {} cells", self.selection_size));
+        }
+        if let Some(path) = &self.path {
+            hud.push_str(&format!("\npath: {} cells", path.len()));
+        } else if let (Tool::Measure, [start, end]) = (self.tool, &self.measure_points[..]) {
+            let distance = self.world.elevation.distance(*start, *end);
+            hud.push_str(&format!(
+                "\ndistance: {:.1} cells ({:.1} units)",
+                distance,
+                distance * self.world.parameters.units_per_cell
+            ));
+        }
+
+        let mut text =
+            graphics::Text::new(TextFragment::new(hud).font(*self.font).scale(hud_scale));
+
+        let warnings = self.parameters.world.warnings();
+        if !warnings.is_empty() {
+            text.add(
+                TextFragment::new(format!("\n\n! {}", warnings.join("\n! ")))
+                    .font(*self.font)
+                    .scale(hud_scale)
+                    .color(WARNING_COLOR),
+            );
+        }
 
+        if let Some((status, time)) = &self.status {
+            if time.elapsed() < STATUS_DURATION {
+                text.add(
+                    TextFragment::new(format!("\n\n{}", status))
+                        .font(*self.font)
+                        .scale(hud_scale),
+                );
+            }
+        }
+
+        let (width, height) = text.dimensions(ctx);
+        self.hud_bounds = graphics::Rect::new(
+            0.0,
+            0.0,
+            width as f32 + HUD_PADDING * 2.0,
+            height as f32 + HUD_PADDING * 2.0,
+        );
+        let background = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            self.hud_bounds,
+            HUD_BACKGROUND,
+        )?;
+        graphics::draw(ctx, &background, DrawParam::default())?;
         graphics::draw(
             ctx,
-            &graphics::Text::new(TextFragment::new(text).font(*self.font)),
-            DrawParam::default(),
+            &text,
+            DrawParam {
+                dest: [HUD_PADDING, HUD_PADDING].into(),
+                ..Default::default()
+            },
         )?;
 
+        if self.show_help {
+            self.draw_help(ctx)?;
+        }
+
         graphics::present(ctx)?;
         Ok(())
     }
 
-    fn mouse_button_down_event(
-        &mut self,
-        _ctx: &mut Context,
-        button: MouseButton,
-        _x: f32,
-        _y: f32,
-    ) {
-        if button == MouseButton::Left {
-            self.mouse_down = true
+    fn mouse_button_down_event(&mut self, _ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
+        if button != MouseButton::Left {
+            return;
+        }
+
+        match self.tool {
+            Tool::Pan => self.mouse_down = true,
+            Tool::Select => {
+                if let Some((x, y)) = self.screen_to_cell(x, y) {
+                    self.select_region(x, y);
+                }
+            }
+            Tool::Measure | Tool::Path => {
+                if let Some(cell) = self.screen_to_cell(x, y) {
+                    if self.measure_points.len() >= 2 {
+                        self.measure_points.clear();
+                        self.path = None;
+                    } else {
+                        self.measure_points.push(cell);
+                    }
+                }
+
+                if let (Tool::Path, [start, goal]) = (self.tool, &self.measure_points[..]) {
+                    let world = &self.world;
+                    self.path =
+                        world.find_path(*start, *goal, |from, to| world.slope_cost(from, to));
+                    if self.path.is_none() {
+                        self.set_status("no path over land".to_string());
+                    }
+                }
+            }
         }
     }
 
@@ -298,75 +2003,96 @@ impl<'f> EventHandler for WorldViewer<'f> {
         }
     }
 
-    fn mouse_wheel_event(&mut self, _ctx: &mut Context, _x: f32, y: f32) {
+    fn mouse_wheel_event(&mut self, ctx: &mut Context, _x: f32, y: f32) {
+        if self.hud_bounds.contains(mouse::position(ctx))
+            || keyboard::is_mod_active(ctx, KeyMods::SHIFT)
+        {
+            if y > 0.0 {
+                self.edit_current_row(EditType::Right);
+            } else if y < 0.0 {
+                self.edit_current_row(EditType::Left);
+            }
+            return;
+        }
+
+        if self.flood_preview {
+            if y > 0.0 {
+                self.flood_delta += 0.05;
+            } else if y < 0.0 {
+                self.flood_delta = (self.flood_delta - 0.05).max(0.0);
+            }
+            self.set_status(format!("flood: sea level +{:.2}", self.flood_delta));
+            self.update_buffer();
+            return;
+        }
+
         if y > 0.0 {
             self.scale *= 1.1;
+            self.user_zoomed = true;
         } else if y < 0.0 {
             self.scale /= 1.1;
+            self.user_zoomed = true;
         }
     }
 
     fn key_down_event(
         &mut self,
-        _ctx: &mut Context,
+        ctx: &mut Context,
         keycode: KeyCode,
         _keymods: KeyMods,
         repeat: bool,
     ) {
-        if keycode == KeyCode::Return && !repeat {
-            self.generate(if self.parameters.shuffle_seed {
-                thread_rng().next_u64()
-            } else {
-                self.world.seed
-            });
-        }
-
-        if keycode == KeyCode::Up {
-            if self.current_row == 0 {
-                self.current_row = self.rows.len() - 1
-            } else {
-                self.current_row -= 1
+        for action in self.key_bindings.actions(keycode) {
+            if (!repeat || action.repeats()) && self.perform(ctx, action) {
+                return;
             }
         }
+    }
 
-        if keycode == KeyCode::Down {
-            if self.current_row >= self.rows.len() - 1 {
-                self.current_row = 0
-            } else {
-                self.current_row += 1;
-            }
-        }
-
-        if keycode == KeyCode::Right || keycode == KeyCode::Left || keycode == KeyCode::Space {
-            let row = &mut self.rows[self.current_row];
-            (row.edit)(
-                &mut self.parameters,
-                match keycode {
-                    KeyCode::Space => EditType::Press,
-                    KeyCode::Right => EditType::Right,
-                    KeyCode::Left => EditType::Left,
-                    _ => panic!("Invalid KeyCode"),
-                },
-            );
-            if self.parameters.auto_generate {
-                self.generate(self.world.seed);
-            }
+    fn key_up_event(&mut self, _ctx: &mut Context, keycode: KeyCode, _keymods: KeyMods) {
+        if self.key_bindings.is_bound(keycode, Action::FloodPreview) {
+            self.flood_preview = false;
+            self.update_buffer();
         }
     }
 
     fn resize_event(&mut self, ctx: &mut Context, width: f32, height: f32) {
         graphics::set_screen_coordinates(ctx, graphics::Rect::new(0.0, 0.0, width, height))
             .expect("Could not set screen coordinates");
+        self.resize(width, height);
     }
 }
 
+struct Label {
+    text: String,
+    x: usize,
+    y: usize,
+}
+
+struct Sweep {
+    group: usize,
+    row: usize,
+    frame: usize,
+    initial: WorldViewerParameters,
+}
+
 enum EditType {
     Right,
     Left,
     Press,
 }
 
+struct RowGroup {
+    name: &'static str,
+    rows: Vec<EditableRow>,
+}
+
+type RowText = Box<dyn Fn(&WorldViewerParameters) -> String>;
+type RowEdit = Box<dyn FnMut(&mut WorldViewerParameters, EditType)>;
+type RowReset = Box<dyn Fn(&mut WorldViewerParameters, &WorldViewerParameters)>;
+
 struct EditableRow {
-    text: Box<dyn Fn(&WorldViewerParameters) -> String>,
-    edit: Box<dyn FnMut(&mut WorldViewerParameters, EditType)>,
+    text: RowText,
+    edit: RowEdit,
+    reset: RowReset,
 }
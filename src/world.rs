@@ -1,46 +1,1046 @@
-use crate::noisemap::{FalloffParameters, NoiseMap, NoiseParameters};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{BufWriter, Write};
+use std::num::ParseFloatError;
+use std::path::Path;
+use std::str::FromStr;
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::coloring::{self, Color, ColorSpace, Colors, ViewMode};
+use crate::contours::{self, ContourBand};
+use crate::erosion::{self, ErosionParameters};
+use crate::error::RealmsError;
+use crate::names::{self, FeatureKind};
+use crate::noisemap::{
+    Connectivity, FalloffParameters, Float, NoiseMap, NoiseParameters, OctaveSum, PolarParameters,
+};
+use crate::pathfinding;
+use crate::util::{float_bits, gaussian, inverse_lerp, lerp};
+
+const RANDOM_OCTAVES: (usize, usize) = (3, 9);
+const RANDOM_PERSISTENCE: (f64, f64) = (0.3, 0.6);
+const RANDOM_LACUNARITY: (f64, f64) = (1.8, 3.5);
+const RANDOM_FREQUENCY: (f64, f64) = (1.5, 6.0);
+const RANDOM_SEA_LEVEL: (f64, f64) = (-0.2, 0.2);
+const RANDOM_SEA_PERCENTILE: (f64, f64) = (50.0, 80.0);
+const RANDOM_FALLOFF_A: (f64, f64) = (1.5, 3.5);
+const RANDOM_FALLOFF_B: (f64, f64) = (2.0, 8.0);
+const RANDOM_FALLOFF_MULTIPLIER: (f64, f64) = (0.3, 0.9);
+
+const PATH_SLOPE_PENALTY: f64 = 200.0;
+const METADATA_PEAK_PROMINENCE: f64 = 0.1;
+const SHALLOW_WATER: f64 = 0.5;
+const HIGHLAND: f64 = 0.4;
+const MOUNTAIN: f64 = 0.75;
+
+// Discriminants are the palette indices used by exports and must stay stable.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Biome {
+    DeepWater = 0,
+    ShallowWater = 1,
+    Lowland = 2,
+    Highland = 3,
+    Mountain = 4,
+}
+
+impl Biome {
+    pub const ALL: [Biome; 5] = [
+        Biome::DeepWater,
+        Biome::ShallowWater,
+        Biome::Lowland,
+        Biome::Highland,
+        Biome::Mountain,
+    ];
+
+    pub fn index(self) -> u8 {
+        self as u8
+    }
+
+    pub fn color(self) -> [u8; 3] {
+        match self {
+            Biome::DeepWater => [35, 45, 84],
+            Biome::ShallowWater => [51, 98, 153],
+            Biome::Lowland => [33, 156, 53],
+            Biome::Highland => [100, 130, 60],
+            Biome::Mountain => [140, 130, 120],
+        }
+    }
+}
+
+// Percentile floods the given percentage of the cells, so the land fraction stays the same
+// across seeds.
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SeaLevelMode {
+    Absolute(f64),
+    Percentile(f64),
+}
+
+impl SeaLevelMode {
+    pub fn key(&self) -> impl Eq + Hash {
+        match *self {
+            SeaLevelMode::Absolute(level) => (false, float_bits(level)),
+            SeaLevelMode::Percentile(percent) => (true, float_bits(percent)),
+        }
+    }
+
+    pub fn resolve<T: Float>(&self, elevation: &NoiseMap<T>) -> f64 {
+        match *self {
+            SeaLevelMode::Absolute(level) => level,
+            SeaLevelMode::Percentile(percent) => elevation.percentile(percent),
+        }
+    }
+
+    pub fn lerp(&self, other: &Self, t: f64) -> Self {
+        match (*self, *other) {
+            (SeaLevelMode::Absolute(a), SeaLevelMode::Absolute(b)) => {
+                SeaLevelMode::Absolute(lerp(a, b, t))
+            }
+            (SeaLevelMode::Percentile(a), SeaLevelMode::Percentile(b)) => {
+                SeaLevelMode::Percentile(lerp(a, b, t))
+            }
+            _ if t < 0.5 => *self,
+            _ => *other,
+        }
+    }
+}
+
+impl fmt::Display for SeaLevelMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SeaLevelMode::Absolute(level) => write!(f, "{}", level),
+            SeaLevelMode::Percentile(percent) => write!(f, "{}%", percent),
+        }
+    }
+}
+
+impl FromStr for SeaLevelMode {
+    type Err = ParseFloatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_suffix('%') {
+            Some(percent) => percent.parse().map(SeaLevelMode::Percentile),
+            None => s.parse().map(SeaLevelMode::Absolute),
+        }
+    }
+}
+
+impl PartialEq for SeaLevelMode {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for SeaLevelMode {}
+
+impl Hash for SeaLevelMode {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct WorldParameters {
     pub width: usize,
     pub height: usize,
     pub elevation_parameters: NoiseParameters,
     pub falloff: Option<FalloffParameters>,
-    pub sea_level: f64,
+    pub polar: Option<PolarParameters>,
+    pub sea_level: SeaLevelMode,
+    pub units_per_cell: f64,
+}
+
+impl WorldParameters {
+    pub fn key(&self) -> impl Eq + Hash {
+        (
+            self.width,
+            self.height,
+            self.elevation_parameters.key(),
+            self.falloff.map(|falloff| falloff.key()),
+            self.polar.map(|polar| polar.key()),
+            self.sea_level.key(),
+            float_bits(self.units_per_cell),
+        )
+    }
+
+    // An equirectangular planet: x is longitude over the full 360 degrees and wraps seamlessly,
+    // y is latitude from the north pole at the top to the south pole at the bottom, and each cell
+    // covers the same angle in both directions, so areas stretch toward the poles as on any 2:1
+    // world map. The poles are pushed under water so continents don't run into the edges.
+    pub fn world_map(height: usize) -> Self {
+        Self {
+            width: height * 2,
+            height,
+            elevation_parameters: NoiseParameters {
+                frequency: 4.0,
+                octaves: 8,
+                persistence: 0.45,
+                lacunarity: 2.5,
+                rotate_octaves: true,
+                preserve_aspect: true,
+                antialias: true,
+                wrap_x: true,
+                decorrelate_octaves: true,
+                domain_offset: (0.0, 0.0),
+            },
+            falloff: None,
+            polar: Some(PolarParameters::default()),
+            sea_level: SeaLevelMode::Percentile(70.0),
+            units_per_cell: 1.0,
+        }
+    }
+
+    // the sea level only affects coloring, so changing it alone keeps the elevation valid
+    pub fn needs_regeneration(&self, other: &Self) -> bool {
+        *self
+            != Self {
+                sea_level: self.sea_level,
+                ..*other
+            }
+    }
+
+    // sizes and octaves round to the nearest integer; falloff and polar suppression only blend
+    // when both sides have one
+    pub fn lerp(&self, other: &Self, t: f64) -> Self {
+        Self {
+            width: lerp(self.width as f64, other.width as f64, t).round() as usize,
+            height: lerp(self.height as f64, other.height as f64, t).round() as usize,
+            elevation_parameters: self
+                .elevation_parameters
+                .lerp(&other.elevation_parameters, t),
+            falloff: match (&self.falloff, &other.falloff) {
+                (Some(a), Some(b)) => Some(a.lerp(b, t)),
+                _ if t < 0.5 => self.falloff,
+                _ => other.falloff,
+            },
+            polar: match (&self.polar, &other.polar) {
+                (Some(a), Some(b)) => Some(a.lerp(b, t)),
+                _ if t < 0.5 => self.polar,
+                _ => other.polar,
+            },
+            sea_level: self.sea_level.lerp(&other.sea_level, t),
+            units_per_cell: lerp(self.units_per_cell, other.units_per_cell, t),
+        }
+    }
+
+    pub fn randomize<R: Rng + ?Sized>(&mut self, random: &mut R) {
+        let noise = &mut self.elevation_parameters;
+        noise.octaves = random.gen_range(RANDOM_OCTAVES.0, RANDOM_OCTAVES.1);
+        noise.persistence = random.gen_range(RANDOM_PERSISTENCE.0, RANDOM_PERSISTENCE.1);
+        noise.lacunarity = random.gen_range(RANDOM_LACUNARITY.0, RANDOM_LACUNARITY.1);
+        noise.frequency = random.gen_range(RANDOM_FREQUENCY.0, RANDOM_FREQUENCY.1);
+
+        self.sea_level = match self.sea_level {
+            SeaLevelMode::Absolute(_) => {
+                SeaLevelMode::Absolute(random.gen_range(RANDOM_SEA_LEVEL.0, RANDOM_SEA_LEVEL.1))
+            }
+            SeaLevelMode::Percentile(_) => SeaLevelMode::Percentile(
+                random.gen_range(RANDOM_SEA_PERCENTILE.0, RANDOM_SEA_PERCENTILE.1),
+            ),
+        };
+        self.falloff = if random.gen() {
+            Some(FalloffParameters {
+                a: random.gen_range(RANDOM_FALLOFF_A.0, RANDOM_FALLOFF_A.1),
+                b: random.gen_range(RANDOM_FALLOFF_B.0, RANDOM_FALLOFF_B.1),
+                multiplier: random
+                    .gen_range(RANDOM_FALLOFF_MULTIPLIER.0, RANDOM_FALLOFF_MULTIPLIER.1),
+                ..FalloffParameters::default()
+            })
+        } else {
+            None
+        };
+    }
+
+    // unlike warnings, these would make generation itself fail or produce garbage
+    pub fn validate(&self) -> Result<(), RealmsError> {
+        let noise = &self.elevation_parameters;
+        let invalid = |reason: &str| Err(RealmsError::InvalidParameters(reason.to_string()));
+
+        if self.width == 0 || self.height == 0 {
+            return invalid("map is empty");
+        }
+        if !(noise.frequency > 0.0 && noise.frequency.is_finite()) {
+            return invalid("frequency must be positive");
+        }
+        if !noise.persistence.is_finite() || !noise.lacunarity.is_finite() {
+            return invalid("persistence and lacunarity must be finite");
+        }
+        if !(self.units_per_cell > 0.0 && self.units_per_cell.is_finite()) {
+            return invalid("units per cell must be positive");
+        }
+        Ok(())
+    }
+
+    pub fn warnings(&self) -> Vec<String> {
+        let noise = &self.elevation_parameters;
+        let mut warnings = vec![];
+
+        if self.width == 0 || self.height == 0 {
+            warnings.push("map is empty".to_string());
+        }
+        if noise.octaves == 0 {
+            warnings.push("octaves: no octaves, the map is flat".to_string());
+        }
+        if noise.frequency <= 0.0 {
+            warnings.push("frequency: must be positive".to_string());
+        }
+        if noise.persistence > 1.0 {
+            warnings.push("persistence: above 1, fine detail drowns out the shape".to_string());
+        } else if noise.persistence <= 0.0 {
+            warnings.push("persistence: at or below 0, only the first octave counts".to_string());
+        }
+        if noise.lacunarity < 1.0 {
+            warnings.push("lacunarity: below 1, octaves get coarser instead of finer".to_string());
+        } else if (noise.lacunarity - 1.0).abs() < f64::EPSILON {
+            warnings.push("lacunarity: at 1, all octaves share a frequency".to_string());
+        }
+        if let SeaLevelMode::Percentile(percent) = self.sea_level {
+            if !(0.0..=100.0).contains(&percent) {
+                warnings.push("sea level: percentile outside 0 to 100".to_string());
+            }
+        }
+        if self.units_per_cell <= 0.0 {
+            warnings.push("units per cell: must be positive".to_string());
+        }
+        if let Some(falloff) = &self.falloff {
+            if falloff.multiplier < 0.0 || falloff.multiplier > 1.0 {
+                warnings.push("falloff multiplier: outside 0 to 1".to_string());
+            }
+        }
+        if let Some(polar) = &self.polar {
+            if polar.band < 0.0 || polar.transition < 0.0 || polar.band + polar.transition > 0.5 {
+                warnings.push("polar band: must fit within half the map height".to_string());
+            }
+        }
+
+        warnings
+    }
+
+    // a struct literal to paste into code, nested structs come from their Debug output
+    pub fn to_rust(&self) -> String {
+        let nested = |debug: String| debug.replace('\n', "\n    ");
+        format!(
+            "WorldParameters {{\n    width: {},\n    height: {},\n    elevation_parameters: {},\n    \
+             falloff: {},\n    polar: {},\n    sea_level: SeaLevelMode::{:?},\n    \
+             units_per_cell: {:?},\n}}",
+            self.width,
+            self.height,
+            nested(format!("{:#?}", self.elevation_parameters)),
+            nested(format!("{:#?}", self.falloff)),
+            nested(format!("{:#?}", self.polar)),
+            self.sea_level,
+            self.units_per_cell,
+        )
+    }
 }
 
-pub struct World {
+impl PartialEq for WorldParameters {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for WorldParameters {}
+
+impl Hash for WorldParameters {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SvgOptions {
+    pub coast: bool,
+    pub contours: bool,
+    pub contour_interval: f64,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            coast: true,
+            contours: true,
+            contour_interval: 0.1,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CellInfo {
+    pub elevation: f64,
+    pub normalized_elevation: f64,
+    pub biome: Biome,
+    pub is_water: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct WorldMetadata {
+    pub seed: u64,
+    pub parameters: WorldParameters,
+    pub land_fraction: f64,
+    pub landmass_count: usize,
+    pub peaks: Vec<(usize, usize, f64)>,
+}
+
+#[derive(Clone)]
+pub struct World<T: Float = f64> {
     pub seed: u64,
     pub parameters: WorldParameters,
-    pub elevation: NoiseMap,
+    pub octave_offsets: Vec<(f64, f64)>,
+    pub elevation: NoiseMap<T>,
+    pub sea_level: f64,
+    pub droplets: u64,
+    octave_sum: Option<OctaveSum>,
 }
 
-impl World {
+impl<T: Float> World<T> {
+    // keeps the unshaped sum of the octaves, so that set_octaves only has to sample the change
     pub fn new(seed: u64, parameters: WorldParameters) -> Self {
-        let elevation = Self::generate_elevation(seed, &parameters);
+        let noise = &parameters.elevation_parameters;
+        let octave_offsets = NoiseMap::octave_offsets(seed, noise.octaves);
+        let sum = NoiseMap::new_with_offsets(
+            seed,
+            parameters.width,
+            parameters.height,
+            noise,
+            &octave_offsets,
+        );
+        let elevation = Self::shape_elevation(sum.converted(), &parameters);
 
         Self {
             seed,
             parameters,
+            octave_sum: Some(OctaveSum::from_map(seed, noise, &octave_offsets, sum)),
+            octave_offsets,
+            sea_level: parameters.sea_level.resolve(&elevation),
             elevation,
+            droplets: 0,
         }
     }
 
-    fn generate_elevation(seed: u64, parameters: &WorldParameters) -> NoiseMap {
-        match &parameters.falloff {
-            Some(falloff) => NoiseMap::new_with_falloff(
-                seed,
-                parameters.width,
-                parameters.height,
-                &parameters.elevation_parameters,
-                falloff,
-            ),
-            None => NoiseMap::new(
+    pub fn try_new(seed: u64, parameters: WorldParameters) -> Result<Self, RealmsError> {
+        parameters.validate()?;
+        Ok(Self::new(seed, parameters))
+    }
+
+    // generates at a fraction of the size and stretches the result back up, for cheap previews
+    pub fn preview(seed: u64, parameters: WorldParameters, scale: f64) -> Self {
+        let reduced = WorldParameters {
+            width: ((parameters.width as f64 * scale).round() as usize).max(1),
+            height: ((parameters.height as f64 * scale).round() as usize).max(1),
+            ..parameters
+        };
+        let octave_offsets =
+            NoiseMap::octave_offsets(seed, parameters.elevation_parameters.octaves);
+        let elevation = Self::generate_elevation(seed, &reduced, &octave_offsets)
+            .resampled(parameters.width, parameters.height);
+
+        Self {
+            seed,
+            parameters,
+            octave_offsets,
+            sea_level: parameters.sea_level.resolve(&elevation),
+            elevation,
+            droplets: 0,
+            octave_sum: None,
+        }
+    }
+
+    // the sea level is an elevation, so it carries over to the resampled map unchanged
+    pub fn resampled(&self, width: usize, height: usize) -> Self {
+        Self {
+            seed: self.seed,
+            parameters: WorldParameters {
+                width,
+                height,
+                ..self.parameters
+            },
+            octave_offsets: self.octave_offsets.clone(),
+            elevation: self.elevation.resampled(width, height),
+            sea_level: self.sea_level,
+            droplets: self.droplets,
+            octave_sum: None,
+        }
+    }
+
+    pub fn converted<U: Float>(&self) -> World<U> {
+        World {
+            seed: self.seed,
+            parameters: self.parameters,
+            octave_offsets: self.octave_offsets.clone(),
+            elevation: self.elevation.converted(),
+            sea_level: self.sea_level,
+            droplets: self.droplets,
+            octave_sum: self.octave_sum.clone(),
+        }
+    }
+
+    pub fn land_mask(&self) -> Vec<bool> {
+        self.elevation.threshold(self.sea_level)
+    }
+
+    pub fn cell(&self, x: usize, y: usize) -> CellInfo {
+        let elevation = self.elevation.get(x, y);
+
+        CellInfo {
+            elevation,
+            normalized_elevation: self.elevation.normalize(elevation),
+            biome: self.biome(x, y),
+            is_water: elevation < self.sea_level,
+        }
+    }
+
+    pub fn biome(&self, x: usize, y: usize) -> Biome {
+        let value = self.elevation.get(x, y);
+        let sea_level = self.sea_level;
+
+        if value < sea_level {
+            if inverse_lerp(self.elevation.min, sea_level, value) < SHALLOW_WATER {
+                Biome::DeepWater
+            } else {
+                Biome::ShallowWater
+            }
+        } else {
+            let height = inverse_lerp(sea_level, self.elevation.max, value);
+            if height < HIGHLAND {
+                Biome::Lowland
+            } else if height < MOUNTAIN {
+                Biome::Highland
+            } else {
+                Biome::Mountain
+            }
+        }
+    }
+
+    pub fn biomes(&self) -> Vec<Biome> {
+        let width = self.parameters.width;
+        (0..width * self.parameters.height)
+            .map(|i| self.biome(i % width, i / width))
+            .collect()
+    }
+
+    pub fn export_biome_png<P: AsRef<Path>>(&self, path: P) -> Result<(), RealmsError> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(
+            file,
+            self.parameters.width as u32,
+            self.parameters.height as u32,
+        );
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_palette(
+            Biome::ALL
+                .iter()
+                .flat_map(|biome| biome.color().to_vec())
+                .collect(),
+        );
+
+        let indices: Vec<u8> = self.biomes().into_iter().map(Biome::index).collect();
+        encoder.write_header()?.write_image_data(&indices)?;
+        Ok(())
+    }
+
+    // samples elevation bilinearly, so the image can be much larger than the map
+    pub fn export_png_resampled<P: AsRef<Path>>(
+        &self,
+        path: P,
+        out_width: u32,
+        out_height: u32,
+        colors: &Colors,
+    ) -> Result<(), RealmsError> {
+        let resampled = self.resampled(out_width as usize, out_height as usize);
+        let buffer = coloring::color_buffer(&resampled, colors, ViewMode::Terrain);
+
+        image::save_buffer(
+            path,
+            &buffer,
+            out_width,
+            out_height,
+            image::ColorType::Rgba8,
+        )?;
+        Ok(())
+    }
+
+    pub fn export_asc<P: AsRef<Path>>(&self, path: P) -> Result<(), RealmsError> {
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "ncols {}", self.parameters.width)?;
+        writeln!(file, "nrows {}", self.parameters.height)?;
+        writeln!(file, "xllcorner 0")?;
+        writeln!(file, "yllcorner 0")?;
+        writeln!(file, "cellsize {}", self.parameters.units_per_cell)?;
+        writeln!(file, "NODATA_value -9999")?;
+
+        for y in 0..self.parameters.height {
+            let row: Vec<String> = (0..self.parameters.width)
+                .map(|x| self.elevation.get(x, y).to_string())
+                .collect();
+            writeln!(file, "{}", row.join(" "))?;
+        }
+
+        file.flush()?;
+        Ok(())
+    }
+
+    pub fn metadata(&self) -> WorldMetadata {
+        let land_mask = self.land_mask();
+        let land_cells = land_mask.iter().filter(|&&land| land).count();
+
+        let landmass_count = self
+            .regions(Connectivity::default())
+            .into_iter()
+            .zip(&land_mask)
+            .filter(|&(_, &land)| land)
+            .map(|(label, _)| label)
+            .collect::<HashSet<_>>()
+            .len();
+
+        let range = self.elevation.max - self.elevation.min;
+
+        WorldMetadata {
+            seed: self.seed,
+            parameters: self.parameters,
+            land_fraction: land_cells as f64 / land_mask.len().max(1) as f64,
+            landmass_count,
+            peaks: self.elevation.peaks(range * METADATA_PEAK_PROMINENCE),
+        }
+    }
+
+    #[cfg(feature = "json")]
+    pub fn to_metadata_json<P: AsRef<Path>>(&self, path: P) -> Result<(), RealmsError> {
+        let file = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(file, &self.metadata()).map_err(std::io::Error::from)?;
+        Ok(())
+    }
+
+    pub fn contour_polygons(&self, interval: f64) -> Vec<ContourBand> {
+        contours::contour_bands(&self.elevation, interval)
+    }
+
+    pub fn export_svg<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &SvgOptions,
+        colors: &Colors,
+    ) -> Result<(), RealmsError> {
+        let (width, height) = (self.parameters.width, self.parameters.height);
+        let sea_level = self.sea_level;
+        let fill = |color: Color| format!("rgb({},{},{})", color.r, color.g, color.b);
+
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(
+            file,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#,
+            width, height
+        )?;
+        writeln!(
+            file,
+            r#"<rect width="{}" height="{}" fill="{}"/>"#,
+            width,
+            height,
+            fill(colors.sea_low)
+        )?;
+
+        if options.contours {
+            for band in self.contour_polygons(options.contour_interval) {
+                let color = coloring::terrain_color(self, colors, band.level, 0.0, ColorSpace::Rgb);
+                writeln!(
+                    file,
+                    r#"<path d="{}" fill="{}" fill-rule="evenodd" stroke="rgba(0,0,0,0.3)" stroke-width="0.5"/>"#,
+                    contours::svg_path(&band.loops),
+                    fill(color)
+                )?;
+            }
+        }
+
+        if options.coast {
+            writeln!(
+                file,
+                r#"<path d="{}" fill="none" stroke="black" stroke-width="1"/>"#,
+                contours::svg_path(&contours::contour_loops(&self.elevation, sea_level))
+            )?;
+        }
+
+        writeln!(file, "</svg>")?;
+        file.flush()?;
+        Ok(())
+    }
+
+    pub fn name_feature(&self, kind: FeatureKind, x: usize, y: usize) -> String {
+        names::feature_name(self.seed, kind, x, y)
+    }
+
+    pub fn find_path<F>(
+        &self,
+        start: (usize, usize),
+        goal: (usize, usize),
+        cost: F,
+    ) -> Option<Vec<(usize, usize)>>
+    where
+        F: Fn((usize, usize), (usize, usize)) -> Option<f64>,
+    {
+        pathfinding::find_path(&self.elevation, start, goal, cost)
+    }
+
+    // Water is impassable and climbing costs more the steeper it is; descending is free of penalty.
+    pub fn slope_cost(&self, from: (usize, usize), to: (usize, usize)) -> Option<f64> {
+        let sea_level = self.sea_level;
+        let (from_height, to_height) = (
+            self.elevation.get(from.0, from.1),
+            self.elevation.get(to.0, to.1),
+        );
+        if from_height < sea_level || to_height < sea_level {
+            return None;
+        }
+
+        let distance = self.elevation.distance(from, to);
+        let rise = self.elevation.normalize(to_height) - self.elevation.normalize(from_height);
+
+        Some(distance + PATH_SLOPE_PENALTY * rise.max(0.0))
+    }
+
+    pub fn regions(&self, connectivity: Connectivity) -> Vec<usize> {
+        self.elevation
+            .connected_components(self.sea_level, connectivity)
+    }
+
+    // returns the region the droplet touched, empty when the map is too thin for one to roll
+    pub fn erode_step(&mut self, parameters: &ErosionParameters) -> (usize, usize, usize, usize) {
+        if self.parameters.width < 2 || self.parameters.height < 2 {
+            return (0, 0, 0, 0);
+        }
+
+        let mut random = StdRng::seed_from_u64(self.seed ^ self.droplets);
+        let start = (
+            random.gen_range(0.0, (self.parameters.width - 1) as f64),
+            random.gen_range(0.0, (self.parameters.height - 1) as f64),
+        );
+        self.droplets += 1;
+
+        let (x0, y0, x1, y1) = erosion::erode_droplet(&mut self.elevation, start, parameters);
+        self.elevation_edited();
+        self.elevation.update_bounds_in(x0, y0, x1, y1);
+        (x0, y0, x1, y1)
+    }
+
+    pub fn set_sea_level(&mut self, sea_level: SeaLevelMode) {
+        self.parameters.sea_level = sea_level;
+        self.sea_level = sea_level.resolve(&self.elevation);
+    }
+
+    // only the octaves that come or go are summed; the raw sum is built on the first change and
+    // kept for the next ones
+    pub fn set_octaves(&mut self, octaves: usize) {
+        let (seed, parameters) = (self.seed, self.parameters);
+        let octave_offsets = &self.octave_offsets;
+        // without a sum the elevation was edited or never summed, so the octaves kept are summed
+        // again from scratch
+        let octave_sum = self.octave_sum.get_or_insert_with(|| {
+            OctaveSum::new(
                 seed,
                 parameters.width,
                 parameters.height,
                 &parameters.elevation_parameters,
-            ),
+                &octave_offsets[..octaves.min(octave_offsets.len())],
+            )
+        });
+
+        let current = octave_sum.octave_offsets().len();
+        if octaves > current {
+            for &offset in &NoiseMap::octave_offsets(seed, octaves)[current..] {
+                octave_sum.add_octave(offset);
+            }
+        }
+        for _ in octaves..current {
+            octave_sum.remove_octave();
+        }
+
+        self.octave_offsets = octave_sum.octave_offsets().to_vec();
+        self.parameters.elevation_parameters.octaves = octaves;
+        self.elevation = Self::shape_elevation(octave_sum.to_map(), &self.parameters);
+        self.sea_level = self.parameters.sea_level.resolve(&self.elevation);
+        self.droplets = 0;
+    }
+
+    pub fn reroll_octave(&mut self, octave: usize, sub_seed: u64) {
+        self.octave_offsets[octave] = NoiseMap::octave_offsets(sub_seed, 1)[0];
+        self.octave_sum = None;
+        self.elevation =
+            Self::generate_elevation(self.seed, &self.parameters, &self.octave_offsets);
+        self.sea_level = self.parameters.sea_level.resolve(&self.elevation);
+    }
+
+    // keeps the octaves below from_octave, so the broad shape survives and only the finer detail
+    // changes
+    pub fn reroll_detail(&mut self, from_octave: usize) {
+        if from_octave >= self.octave_offsets.len() {
+            return;
         }
+
+        let count = self.octave_offsets.len() - from_octave;
+        let offsets = NoiseMap::octave_offsets(thread_rng().gen(), count);
+        self.octave_offsets[from_octave..].copy_from_slice(&offsets);
+        self.octave_sum = None;
+        self.elevation =
+            Self::generate_elevation(self.seed, &self.parameters, &self.octave_offsets);
+        self.sea_level = self.parameters.sea_level.resolve(&self.elevation);
+    }
+
+    // rescales elevation into [0, 1], keeping the same cells under water; a percentile sea level
+    // floods the same cells either way, so only an absolute one has to move
+    pub fn normalize_elevation(&mut self) {
+        self.elevation.update_bounds();
+        let (min, max) = (self.elevation.min, self.elevation.max);
+        let rescale = |value: f64| {
+            if max > min {
+                inverse_lerp(min, max, value)
+            } else {
+                value - min
+            }
+        };
+
+        self.elevation.map_values(rescale);
+        self.elevation_edited();
+        self.sea_level = rescale(self.sea_level);
+        if let SeaLevelMode::Absolute(level) = self.parameters.sea_level {
+            self.parameters.sea_level = SeaLevelMode::Absolute(rescale(level));
+        }
+    }
+
+    pub fn clamp_elevation(&mut self, low: f64, high: f64) {
+        self.elevation.map_values(|value| value.max(low).min(high));
+        self.elevation_edited();
+    }
+
+    // the elevation no longer is the sum of its octaves, for edits made to it directly too
+    pub fn elevation_edited(&mut self) {
+        self.octave_sum = None;
+    }
+
+    pub fn variant(&self, amount: f64) -> Self {
+        let mut random = thread_rng();
+        let spread = amount * self.parameters.elevation_parameters.frequency;
+
+        let octave_offsets: Vec<(f64, f64)> = self
+            .octave_offsets
+            .iter()
+            .map(|&(x, y)| {
+                (
+                    x + gaussian(&mut random) * spread,
+                    y + gaussian(&mut random) * spread,
+                )
+            })
+            .collect();
+        let elevation = Self::generate_elevation(self.seed, &self.parameters, &octave_offsets);
+
+        Self {
+            seed: self.seed,
+            parameters: self.parameters,
+            octave_offsets,
+            sea_level: self.parameters.sea_level.resolve(&elevation),
+            elevation,
+            droplets: 0,
+            octave_sum: None,
+        }
+    }
+
+    fn generate_elevation(
+        seed: u64,
+        parameters: &WorldParameters,
+        octave_offsets: &[(f64, f64)],
+    ) -> NoiseMap<T> {
+        let elevation = NoiseMap::new_with_offsets(
+            seed,
+            parameters.width,
+            parameters.height,
+            &parameters.elevation_parameters,
+            octave_offsets,
+        );
+        Self::shape_elevation(elevation, parameters)
+    }
+
+    fn shape_elevation(mut elevation: NoiseMap<T>, parameters: &WorldParameters) -> NoiseMap<T> {
+        if let Some(falloff) = &parameters.falloff {
+            elevation.apply_falloff(falloff);
+        }
+        if let Some(polar) = &parameters.polar {
+            elevation.apply_polar_suppression(polar);
+        }
+        elevation.update_bounds();
+
+        elevation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizing_keeps_the_coast_and_sea_level_parameter() {
+        let mut parameters = WorldParameters::world_map(16);
+        parameters.sea_level = SeaLevelMode::Absolute(0.1);
+        let mut world = World::<f64>::new(3, parameters);
+        let land = world.land_mask();
+
+        world.normalize_elevation();
+
+        assert_eq!(world.elevation.min, 0.0);
+        assert_eq!(world.elevation.max, 1.0);
+        assert_eq!(world.land_mask(), land);
+        assert_eq!(
+            world.parameters.sea_level,
+            SeaLevelMode::Absolute(world.sea_level)
+        );
+        assert_eq!(
+            world.parameters.sea_level.resolve(&world.elevation),
+            world.sea_level
+        );
+    }
+
+    #[test]
+    fn normalizing_leaves_a_percentile_sea_level() {
+        let mut world = World::<f64>::new(3, WorldParameters::world_map(16));
+        let land = world.land_mask();
+
+        world.normalize_elevation();
+
+        assert_eq!(world.land_mask(), land);
+        assert_eq!(world.parameters.sea_level, SeaLevelMode::Percentile(70.0));
+    }
+
+    #[test]
+    fn erosion_skips_single_cell_rows_and_columns() {
+        let erosion = ErosionParameters::default();
+        for &(width, height) in &[(1, 8), (8, 1), (1, 1)] {
+            let parameters = WorldParameters {
+                width,
+                height,
+                ..WorldParameters::world_map(4)
+            };
+            let mut world = World::<f64>::new(3, parameters);
+
+            assert_eq!(world.erode_step(&erosion), (0, 0, 0, 0));
+            assert_eq!(world.droplets, 0);
+        }
+    }
+
+    fn argmin_argmax<T: Float>(elevation: &NoiseMap<T>) -> (usize, usize) {
+        let values: Vec<f64> = elevation.map.iter().map(|&value| value.into()).collect();
+        let argmin = (0..values.len())
+            .min_by(|&a, &b| values[a].partial_cmp(&values[b]).unwrap())
+            .unwrap();
+        let argmax = (0..values.len())
+            .max_by(|&a, &b| values[a].partial_cmp(&values[b]).unwrap())
+            .unwrap();
+        (argmin, argmax)
+    }
+
+    #[test]
+    fn extremes_follow_falloff_and_erosion() {
+        let parameters = WorldParameters {
+            falloff: Some(FalloffParameters::default()),
+            polar: None,
+            ..WorldParameters::world_map(16)
+        };
+        let mut world = World::<f64>::new(5, parameters);
+        let (argmin, argmax) = argmin_argmax(&world.elevation);
+        assert_eq!(world.elevation.min_index, argmin);
+        assert_eq!(world.elevation.max_index, argmax);
+        assert_eq!(world.elevation.min, world.elevation.map[argmin]);
+
+        let erosion = ErosionParameters::default();
+        for _ in 0..200 {
+            world.erode_step(&erosion);
+        }
+        let (argmin, argmax) = argmin_argmax(&world.elevation);
+        assert_eq!(
+            world.elevation.map[world.elevation.min_index],
+            world.elevation.map[argmin]
+        );
+        assert_eq!(
+            world.elevation.map[world.elevation.max_index],
+            world.elevation.map[argmax]
+        );
+    }
+
+    #[test]
+    fn wrap_x_paths_cross_the_seam() {
+        let mut parameters = WorldParameters::world_map(4);
+        parameters.elevation_parameters.wrap_x = true;
+        let mut world = World::<f64>::new(3, parameters);
+        for (i, value) in world.elevation.map.iter_mut().enumerate() {
+            *value = 1.0 + (i / world.parameters.width) as f64;
+        }
+        world.elevation.update_bounds();
+        world.sea_level = 0.0;
+
+        let path = world
+            .find_path((1, 0), (6, 0), |from, to| world.slope_cost(from, to))
+            .unwrap();
+
+        assert_eq!(path, vec![(1, 0), (0, 0), (7, 0), (6, 0)]);
+        assert_eq!(world.slope_cost((0, 0), (7, 0)), Some(1.0));
+    }
+
+    fn assert_same_elevation(a: &World, b: &World) {
+        assert_eq!(a.octave_offsets, b.octave_offsets);
+        for (&a, &b) in a.elevation.map.iter().zip(&b.elevation.map) {
+            assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+        }
+    }
+
+    #[test]
+    fn changing_octaves_matches_generating_with_them() {
+        let parameters = WorldParameters::world_map(16);
+        let octaves = parameters.elevation_parameters.octaves;
+        let with_octaves = |octaves| {
+            let mut parameters = parameters;
+            parameters.elevation_parameters.octaves = octaves;
+            World::<f64>::new(3, parameters)
+        };
+
+        for &changed in &[octaves - 1, octaves + 1] {
+            let mut world = World::<f64>::new(3, parameters);
+            world.set_octaves(changed);
+            assert_same_elevation(&world, &with_octaves(changed));
+
+            let mut world = World::<f64>::new(3, parameters);
+            world.erode_step(&ErosionParameters::default());
+            world.clamp_elevation(-0.5, 0.5);
+            world.set_octaves(changed);
+            assert_same_elevation(&world, &with_octaves(changed));
+        }
+    }
+
+    #[test]
+    fn parameters_print_as_a_struct_literal() {
+        let mut parameters = WorldParameters::world_map(4);
+        parameters.polar = None;
+        parameters.sea_level = SeaLevelMode::Absolute(-0.25);
+        let rust = parameters.to_rust();
+
+        assert!(rust.starts_with("WorldParameters {\n    width: 8,\n    height: 4,\n"));
+        assert!(rust.contains("\n    elevation_parameters: NoiseParameters {\n        frequency: "));
+        assert!(rust.contains("\n        domain_offset: (\n            0.0,\n"));
+        assert!(rust.contains("\n    falloff: None,\n    polar: None,\n"));
+        assert!(rust.ends_with(
+            "\n    sea_level: SeaLevelMode::Absolute(-0.25),\n    units_per_cell: 1.0,\n}"
+        ));
+    }
+
+    #[test]
+    fn clamping_bounds_the_elevation() {
+        let mut world = World::<f64>::new(3, WorldParameters::world_map(16));
+        world.clamp_elevation(-0.1, 0.1);
+
+        assert!(world.elevation.min >= -0.1);
+        assert!(world.elevation.max <= 0.1);
     }
 }